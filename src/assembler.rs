@@ -0,0 +1,147 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! `as` personality
+//!
+//! LLVM-only systems have no GNU `as`, but clang's integrated assembler handles the
+//! same sources. We rewrite the GNU `as` command line into `clang -c -x assembler`.
+
+use std::ffi::OsString;
+
+/// Build a `-Wa,` passthrough for the integrated assembler
+fn passthrough<'a>(parts: impl IntoIterator<Item = &'a str>) -> OsString {
+    let mut arg = OsString::from("-Wa");
+    for part in parts {
+        arg.push(",");
+        arg.push(part);
+    }
+    arg
+}
+
+/// Rewrite GNU `as` arguments into an equivalent `clang -c -x assembler` invocation
+pub fn clang_args(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut result = vec!["-c".into(), "-x".into(), "assembler".into()];
+    let mut inputs = vec![];
+    let mut has_output = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.to_str() else {
+            inputs.push(arg);
+            continue;
+        };
+
+        match flag {
+            "-o" | "-I" => {
+                has_output |= flag == "-o";
+                result.push(arg);
+                result.extend(args.next());
+            }
+            "--32" => result.push("-m32".into()),
+            "--64" => result.push("-m64".into()),
+            "--x32" => result.push("-mx32".into()),
+            "-g" | "--gen-debug" => result.push("-g".into()),
+            "-W" | "--no-warn" => result.push("-w".into()),
+            "--" | "-" => inputs.push("-".into()),
+            "--defsym" => {
+                if let Some(value) = args.next() {
+                    result.push(passthrough(["-defsym", value.to_str().unwrap_or_default()]));
+                }
+            }
+            // GCC passes these to emit (or not) the `.ident` directive
+            x if x.starts_with("-Q") => {}
+            x if x.starts_with("--defsym=") => {
+                result.push(passthrough(["-defsym", &x["--defsym=".len()..]]));
+            }
+            x if x.starts_with("-gdwarf") || x.starts_with("--gdwarf") => {
+                result.push(format!("-{}", x.trim_start_matches('-')).into());
+            }
+            x if x.starts_with("-I") || x.starts_with("-o") => {
+                has_output |= x.starts_with("-o");
+                result.push(arg);
+            }
+            x if x.starts_with('-') => result.push(passthrough([x])),
+            _ => inputs.push(arg),
+        }
+    }
+
+    // GNU as writes to a.out by default, clang would derive the name from the input
+    if !has_output {
+        result.push("-o".into());
+        result.push("a.out".into());
+    }
+
+    // No input means stdin
+    if inputs.is_empty() {
+        inputs.push("-".into());
+    }
+    result.extend(inputs);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::clang_args;
+
+    fn translate(args: &[&str]) -> Vec<OsString> {
+        clang_args(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn defaults_to_a_out() {
+        assert_eq!(
+            translate(&["x.s"]),
+            ["-c", "-x", "assembler", "-o", "a.out", "x.s"]
+        );
+        assert_eq!(
+            translate(&["-o", "x.o"]),
+            ["-c", "-x", "assembler", "-o", "x.o", "-"]
+        );
+    }
+
+    #[test]
+    fn defsym() {
+        assert_eq!(
+            translate(&["--defsym", "FOO=1", "--defsym=BAR=2", "-ox.o", "x.s"]),
+            [
+                "-c",
+                "-x",
+                "assembler",
+                "-Wa,-defsym,FOO=1",
+                "-Wa,-defsym,BAR=2",
+                "-ox.o",
+                "x.s"
+            ]
+        );
+    }
+
+    #[test]
+    fn gnu_options() {
+        assert_eq!(
+            translate(&[
+                "--32",
+                "-Qy",
+                "--gdwarf-5",
+                "--noexecstack",
+                "-o",
+                "x.o",
+                "x.s"
+            ]),
+            [
+                "-c",
+                "-x",
+                "assembler",
+                "-m32",
+                "-gdwarf-5",
+                "-Wa,--noexecstack",
+                "-o",
+                "x.o",
+                "x.s"
+            ]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Binary utilities matched to the compiler
+//!
+//! LTO objects are compiler specific, so archiving them with a plugin-less GNU `ar`
+//! silently produces broken archives. We pick the LLVM tools for clang and the
+//! `gcc-` plugin wrappers for GCC.
+
+/// Binary utility we can stand in for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binutil {
+    Ar,
+    Nm,
+    Ranlib,
+    Strip,
+    Objcopy,
+}
+
+impl Binutil {
+    /// Match a utility name (`ar`) to the tool
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ar" => Some(Binutil::Ar),
+            "nm" => Some(Binutil::Nm),
+            "ranlib" => Some(Binutil::Ranlib),
+            "strip" => Some(Binutil::Strip),
+            "objcopy" => Some(Binutil::Objcopy),
+            _ => None,
+        }
+    }
+
+    /// Name of the utility
+    pub fn name(&self) -> &'static str {
+        match self {
+            Binutil::Ar => "ar",
+            Binutil::Nm => "nm",
+            Binutil::Ranlib => "ranlib",
+            Binutil::Strip => "strip",
+            Binutil::Objcopy => "objcopy",
+        }
+    }
+
+    /// Environment variable naming the user's tool of choice
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            Binutil::Ar => "AR",
+            Binutil::Nm => "NM",
+            Binutil::Ranlib => "RANLIB",
+            Binutil::Strip => "STRIP",
+            Binutil::Objcopy => "OBJCOPY",
+        }
+    }
+
+    /// Name of the LLVM equivalent
+    pub fn llvm_name(&self) -> &'static str {
+        match self {
+            Binutil::Ar => "llvm-ar",
+            Binutil::Nm => "llvm-nm",
+            Binutil::Ranlib => "llvm-ranlib",
+            Binutil::Strip => "llvm-strip",
+            Binutil::Objcopy => "llvm-objcopy",
+        }
+    }
+
+    /// Name of the GNU tool, using the LTO plugin wrapper where GCC ships one
+    pub fn gnu_name(&self) -> &'static str {
+        match self {
+            Binutil::Ar => "gcc-ar",
+            Binutil::Nm => "gcc-nm",
+            Binutil::Ranlib => "gcc-ranlib",
+            Binutil::Strip => "strip",
+            Binutil::Objcopy => "objcopy",
+        }
+    }
+}
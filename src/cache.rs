@@ -0,0 +1,271 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Resolutions cached between invocations
+//!
+//! A large build runs `cc` thousands of times in the same environment, so the
+//! resolved toolchain is kept under `$XDG_RUNTIME_DIR/autocc` (or `AUTOCC_CACHE_DIR`).
+//! Entries are keyed on everything resolution looks at: the variables involved and the
+//! identity of the directories searched, the configuration files and autocc itself.
+//! Installing, removing or upgrading a compiler touches its directory, after which the
+//! stale entry is simply never looked up again. Answers from probing a compiler are
+//! kept alongside, keyed on the compiler alone.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    env,
+    ffi::OsString,
+    fs::{self, DirBuilder},
+    hash::{Hash, Hasher},
+    io,
+    os::unix::fs::{DirBuilderExt, MetadataExt},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    config, cross, depth, discovery,
+    error::Error,
+    personality::Personality,
+    toolchain::{Tool, Toolchain},
+    trace::trace,
+};
+
+/// Variables outside the `AUTOCC_` namespace that resolution reads
+const VARS: &[&str] = &[
+    "PATH",
+    "CC",
+    "CXX",
+    "CPP",
+    "LD",
+    "AS",
+    "AR",
+    "NM",
+    "RANLIB",
+    "STRIP",
+    "OBJCOPY",
+    "CHOST",
+    "CROSS_COMPILE",
+    "LLVM",
+    "HOSTCC",
+    "CC_FOR_BUILD",
+    "BUILD_CC",
+    "HOSTCXX",
+    "CXX_FOR_BUILD",
+    "BUILD_CXX",
+];
+
+/// `AUTOCC_` variables with no bearing on the result
+const UNKEYED: &[&str] = &[
+    depth::VAR,
+    "AUTOCC_DEBUG",
+    "AUTOCC_TRACE",
+    "AUTOCC_CACHE",
+    "AUTOCC_CACHE_DIR",
+];
+
+static BYPASS: AtomicBool = AtomicBool::new(false);
+
+/// Always resolve from scratch, so every step is traced
+pub fn bypass() {
+    BYPASS.store(true, Ordering::Relaxed);
+}
+
+/// Where entries live, or `None` when caching is off
+fn dir() -> Option<PathBuf> {
+    if BYPASS.load(Ordering::Relaxed) || !config::flag("AUTOCC_CACHE", true) {
+        return None;
+    }
+
+    config::var("AUTOCC_CACHE_DIR")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(env::var_os("XDG_RUNTIME_DIR")?).join("autocc")))
+}
+
+/// Create the directory if needed, and check nobody else can plant entries in it
+///
+/// Entries name the program we go on to run, so a directory another user can write
+/// to (a shared `AUTOCC_CACHE_DIR`, or one created ahead of us) is never used.
+fn prepare(dir: &Path) -> io::Result<()> {
+    DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+
+    let meta = fs::metadata(dir)?;
+    // SAFETY: geteuid has no preconditions and can't fail
+    let euid = unsafe { libc::geteuid() };
+    if !meta.is_dir() || meta.uid() != euid || meta.mode() & 0o022 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "not a directory private to this user",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Identity of a file, changing whenever it's replaced or modified
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct Stamp {
+    dev: u64,
+    ino: u64,
+    mtime: i64,
+    mtime_nsec: i64,
+}
+
+impl Stamp {
+    fn of(path: impl AsRef<Path>) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        Some(Self {
+            dev: meta.dev(),
+            ino: meta.ino(),
+            mtime: meta.mtime(),
+            mtime_nsec: meta.mtime_nsec(),
+        })
+    }
+}
+
+/// File name for the personality's entry in the current environment
+fn key(personality: Personality) -> String {
+    let mut hasher = DefaultHasher::new();
+
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    Stamp::of("/proc/self/exe").hash(&mut hasher);
+    personality.name().hash(&mut hasher);
+    cross::target_for(personality)
+        .map(|t| &t.triple)
+        .hash(&mut hasher);
+
+    for var in VARS {
+        (var, env::var_os(var)).hash(&mut hasher);
+    }
+    let mut autocc = env::vars_os()
+        .filter(|(k, _)| {
+            k.to_str()
+                .is_some_and(|k| k.starts_with("AUTOCC_") && !UNKEYED.contains(&k))
+        })
+        .collect::<Vec<(OsString, OsString)>>();
+    autocc.sort();
+    autocc.hash(&mut hasher);
+
+    // Adding a configuration file changes its directory
+    let config = config::dirs()
+        .into_iter()
+        .chain(config::get().files.clone());
+    for path in discovery::search_dirs().into_iter().chain(config) {
+        let stamp = Stamp::of(&path);
+        (path, stamp).hash(&mut hasher);
+    }
+
+    format!("{}-{:016x}.json", personality.name(), hasher.finish())
+}
+
+/// A cached value, valid while the program it describes is unchanged
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry<T> {
+    value: T,
+    program: String,
+    /// The program itself, which may live outside the directories searched
+    stamp: Option<Stamp>,
+}
+
+/// The directory to cache in, if caching is on and the directory is safe to use
+fn open() -> Option<PathBuf> {
+    let dir = dir()?;
+    match prepare(&dir) {
+        Ok(()) => Some(dir),
+        Err(err) => {
+            trace!("not caching in {}: {err}", dir.display());
+            None
+        }
+    }
+}
+
+/// Look up the personality's resolution, resolving and storing it on a miss
+pub fn get_or_resolve(
+    personality: Personality,
+    resolve: impl FnOnce(Personality) -> Result<Option<Toolchain>, Error>,
+) -> Result<Option<Toolchain>, Error> {
+    let Some(dir) = open() else {
+        return resolve(personality);
+    };
+    let path = dir.join(key(personality));
+
+    if let Some(toolchain) = load(&path) {
+        trace!("using cached resolution {}", path.display());
+        return Ok(Some(toolchain));
+    }
+
+    let Some(toolchain) = resolve(personality)? else {
+        return Ok(None);
+    };
+    match store(&path, &toolchain.tool().program, &toolchain) {
+        Ok(()) => trace!("cached resolution as {}", path.display()),
+        Err(err) => trace!("failed to cache resolution as {}: {err}", path.display()),
+    }
+
+    Ok(Some(toolchain))
+}
+
+/// Look up what the tool said when asked before, asking it on a miss
+///
+/// Only the tool itself is keyed, so the answer is shared by every environment.
+pub fn get_or_probe<T: Serialize + DeserializeOwned>(
+    name: &str,
+    tool: &Tool,
+    probe: impl FnOnce(&Tool) -> T,
+) -> T {
+    let Some(dir) = open() else {
+        return probe(tool);
+    };
+
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    Stamp::of("/proc/self/exe").hash(&mut hasher);
+    (&tool.program, &tool.args).hash(&mut hasher);
+    let path = dir.join(format!("{name}-{:016x}.json", hasher.finish()));
+
+    if let Some(value) = load(&path) {
+        trace!("using cached {name} of {}", tool.program);
+        return value;
+    }
+
+    let value = probe(tool);
+    if let Err(err) = store(&path, &tool.program, &value) {
+        trace!("failed to cache {name} as {}: {err}", path.display());
+    }
+
+    value
+}
+
+/// Load the entry if present and the program hasn't changed
+fn load<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let entry = serde_json::from_slice::<Entry<T>>(&fs::read(path).ok()?).ok()?;
+    if Stamp::of(&entry.program) != entry.stamp {
+        trace!(
+            "{} changed since {} was cached",
+            entry.program,
+            path.display()
+        );
+        return None;
+    }
+
+    Some(entry.value)
+}
+
+/// Write the entry atomically, so concurrent builds never read half of one
+fn store<T: Serialize>(path: &Path, program: &str, value: &T) -> io::Result<()> {
+    let entry = Entry {
+        value,
+        program: program.to_owned(),
+        stamp: Stamp::of(program),
+    };
+    let temp = path.with_extension(format!("{}.tmp", process::id()));
+    fs::write(&temp, serde_json::to_vec(&entry)?)?;
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
@@ -0,0 +1,509 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Management interface, used when invoked as `autocc`
+//!
+//! Reports what each personality would run on this system without running it.
+
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, ExitCode},
+};
+
+use serde::Serialize;
+
+use crate::{
+    cache, config, cross, depth, discovery,
+    error::Error,
+    invocation::Invocation,
+    launcher,
+    personality::{Language, Personality},
+    probe::{self, Identity},
+    resolve, search, shell,
+    toolchain::{Family, Origin, Tool, Toolchain},
+    trace,
+    version::Version,
+};
+
+const USAGE: &str = "\
+Usage: autocc [--json] <COMMAND>
+
+Commands:
+  which [TOOL]            Print what TOOL (default: cc) would run
+  explain [TOOL] [ARGS]   Trace how TOOL would be resolved when given ARGS
+  list                    List installed compilers and the one cc picks
+  doctor                  Check every personality resolves to something runnable
+  config [--show-origin]  Print the settings in effect, and where each was set
+
+Options:
+  --json                  Print machine-readable output
+  --version               Print the version
+  --help                  Print this help
+";
+
+/// Whether the name we were invoked with selects the management interface
+pub fn is_management(arg0: &OsStr) -> bool {
+    Path::new(arg0).file_name() == Some(OsStr::new("autocc"))
+}
+
+/// Run the management command line
+pub fn run(args: impl IntoIterator<Item = OsString>) -> Result<ExitCode, Error> {
+    let mut args = args.into_iter().map(|a| a.to_string_lossy().into_owned());
+    let mut json = false;
+
+    let command = loop {
+        match args.next().as_deref() {
+            Some("--json") => json = true,
+            Some("--version" | "-V") => {
+                output(|out| writeln!(out, "autocc {}", env!("CARGO_PKG_VERSION")))?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            Some("--help" | "-h") | None => {
+                output(|out| write!(out, "{USAGE}"))?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            Some(x) if x.starts_with('-') => {
+                return Err(Error::Usage(format!("unknown option {x}")))
+            }
+            Some(x) => break x.to_owned(),
+        }
+    };
+
+    let mut rest = args.collect::<Vec<_>>();
+    // Anything after explain's tool belongs to the tool
+    let options = match command.as_str() {
+        "explain" => rest.iter().take_while(|a| a.starts_with("--")).count(),
+        _ => rest.len(),
+    };
+    let tail = rest.split_off(options);
+    json |= rest.iter().any(|a| a == "--json");
+    rest.retain(|a| a != "--json");
+    rest.extend(tail);
+
+    // Naming the tool can already trace, when it or the environment picks a target
+    if command == "explain" {
+        trace::capture();
+        cache::bypass();
+    }
+
+    match command.as_str() {
+        "which" => which(tool_arg(rest.first())?, json),
+        "explain" => explain(tool_arg(rest.first())?, rest.into_iter().skip(1), json),
+        "list" => list(json),
+        "doctor" => doctor(json),
+        "config" => show_config(&rest, json),
+        x => Err(Error::Usage(format!("unknown command {x}"))),
+    }
+}
+
+/// Parse the TOOL argument, defaulting to `cc`
+fn tool_arg(name: Option<&String>) -> Result<Personality, Error> {
+    match name {
+        Some(name) => {
+            // A cross tool (`aarch64-linux-gnu-cc`) selects its target too
+            let personality = Personality::from_name(name)
+                .or_else(|| Personality::from_name(cross::split_name(name)?.1))
+                .ok_or_else(|| Error::Usage(format!("unknown tool {name}")))?;
+            cross::init(name, personality);
+            Ok(personality)
+        }
+        None => Ok(Personality::CC),
+    }
+}
+
+/// Write to stdout, where a closed pipe (`autocc doctor | head`) just means the
+/// reader has seen enough
+fn output(write: impl FnOnce(&mut dyn Write) -> io::Result<()>) -> Result<(), Error> {
+    let mut out = io::stdout().lock();
+    match write(&mut out).and_then(|()| out.flush()) {
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => Err(Error::Output(err)),
+        _ => Ok(()),
+    }
+}
+
+/// Print as JSON or via the human formatter
+fn emit<T: Serialize>(
+    json: bool,
+    value: &T,
+    human: impl FnOnce(&mut dyn Write, &T) -> io::Result<()>,
+) -> Result<(), Error> {
+    output(|out| {
+        if json {
+            serde_json::to_writer_pretty(&mut *out, value)?;
+            writeln!(out)
+        } else {
+            human(out, value)
+        }
+    })
+}
+
+/// What a personality resolved to
+#[derive(Debug, Serialize)]
+struct Resolution {
+    tool: &'static str,
+    family: &'static str,
+    program: String,
+    origin: String,
+    command: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    identity: Option<Identity>,
+}
+
+impl Resolution {
+    fn new(invocation: &Invocation) -> Self {
+        let (cmd, origin) = invocation.command(0);
+        let command = command_line(&cmd);
+
+        Self {
+            tool: invocation.personality.name(),
+            family: invocation.toolchain.family(),
+            program: invocation.toolchain.tool().program.clone(),
+            origin,
+            command,
+            target: cross::target().map(|t| t.triple.clone()),
+            identity: invocation.toolchain.tool().identity.clone(),
+        }
+    }
+}
+
+/// The full command line, program first
+fn command_line(cmd: &Command) -> Vec<String> {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect()
+}
+
+fn which(personality: Personality, json: bool) -> Result<ExitCode, Error> {
+    let resolution = Resolution::new(&Invocation::prepare(personality, [])?);
+    emit(json, &resolution, |out, r| {
+        writeln!(out, "{}", r.command.join(" "))
+    })?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Resolution of a personality along with the steps taken
+#[derive(Debug, Serialize)]
+struct Explanation {
+    #[serde(flatten)]
+    resolution: Resolution,
+    trace: Vec<String>,
+}
+
+fn explain(
+    personality: Personality,
+    args: impl IntoIterator<Item = String>,
+    json: bool,
+) -> Result<ExitCode, Error> {
+    let invocation = match Invocation::prepare(personality, args.into_iter().map(OsString::from)) {
+        Ok(invocation) => invocation,
+        Err(err) => {
+            for line in trace::captured() {
+                eprintln!("  {line}");
+            }
+            return Err(err);
+        }
+    };
+
+    let explanation = Explanation {
+        resolution: Resolution::new(&invocation),
+        trace: trace::captured(),
+    };
+    emit(json, &explanation, |out, e| {
+        let r = &e.resolution;
+        writeln!(
+            out,
+            "{} resolves to {} ({}, {})",
+            r.tool, r.program, r.family, r.origin
+        )?;
+        for line in &e.trace {
+            writeln!(out, "  {line}")?;
+        }
+        if let Some(banner) = r.identity.as_ref().and_then(|i| i.banner.as_ref()) {
+            writeln!(out, "identity: {banner}")?;
+        }
+        writeln!(out, "command: {}", r.command.join(" "))
+    })?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// A compiler found on the system
+#[derive(Debug, Serialize)]
+struct Candidate {
+    family: &'static str,
+    program: String,
+    version: Option<Version>,
+    target: Option<String>,
+    selected: bool,
+}
+
+impl Candidate {
+    fn probe(toolchain: &Toolchain, selected: Option<&PathBuf>) -> Self {
+        let program = &toolchain.tool().program;
+
+        Self {
+            family: toolchain.family(),
+            program: program.clone(),
+            version: probe::version(program),
+            target: probe::target(program),
+            selected: selected.is_some() && fs::canonicalize(program).ok().as_ref() == selected,
+        }
+    }
+}
+
+/// Installed compilers and the one `cc` picks
+#[derive(Debug, Serialize)]
+struct Inventory {
+    compilers: Vec<Candidate>,
+    selected: Option<String>,
+    reason: Option<String>,
+}
+
+/// Why the toolchain was picked
+fn reason(toolchain: &Toolchain) -> String {
+    match &toolchain.tool().origin {
+        Origin::Env(var) => format!("chosen from {var}"),
+        Origin::Path => {
+            let preference = resolve::preference()
+                .iter()
+                .map(Family::name)
+                .collect::<Vec<_>>();
+            format!(
+                "{} found in PATH, searching for {}",
+                toolchain.tool().name(),
+                preference.join(" then ")
+            )
+        }
+        Origin::Compiler => "matched to the C compiler".into(),
+        Origin::Version(var) => format!("newest release satisfying {var}"),
+    }
+}
+
+fn list(json: bool) -> Result<ExitCode, Error> {
+    let language = Language::C;
+    let selected = resolve::compiler(language).ok().flatten();
+    let selected_path = selected
+        .as_ref()
+        .and_then(|t| fs::canonicalize(&t.tool().program).ok());
+
+    let mut compilers = discovery::installed(language)
+        .iter()
+        .map(|i| {
+            let candidate = Candidate::probe(&i.toolchain, selected_path.as_ref());
+            // Fall back to the version in the name if the compiler won't say
+            Candidate {
+                version: candidate.version.or_else(|| i.suffix.clone()),
+                ..candidate
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // An explicit CC may name something discovery doesn't know about
+    if let Some(toolchain) = &selected {
+        if !compilers.iter().any(|c| c.selected) {
+            compilers.push(Candidate {
+                selected: true,
+                ..Candidate::probe(toolchain, None)
+            });
+        }
+    }
+
+    let inventory = Inventory {
+        compilers,
+        selected: selected.as_ref().map(|t| t.tool().program.clone()),
+        reason: selected.as_ref().map(reason),
+    };
+
+    emit(json, &inventory, |out, inventory| {
+        let unknown = || "-".to_owned();
+        writeln!(
+            out,
+            "  {:<6}  {:<8}  {:<24}  PROGRAM",
+            "FAMILY", "VERSION", "TARGET"
+        )?;
+        for c in &inventory.compilers {
+            writeln!(
+                out,
+                "{} {:<6}  {:<8}  {:<24}  {}",
+                if c.selected { "*" } else { " " },
+                c.family,
+                c.version.as_ref().map_or_else(unknown, Version::to_string),
+                c.target.clone().unwrap_or_else(unknown),
+                c.program
+            )?;
+        }
+        if let (Some(selected), Some(reason)) = (&inventory.selected, &inventory.reason) {
+            writeln!(out, "\ncc: {selected} ({reason})")?;
+        }
+        Ok(())
+    })?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn show_config(args: &[String], json: bool) -> Result<ExitCode, Error> {
+    let mut show_origin = false;
+    for arg in args {
+        match arg.as_str() {
+            "--show-origin" => show_origin = true,
+            x => return Err(Error::Usage(format!("unknown argument {x}"))),
+        }
+    }
+
+    emit(json, &config::entries(), |out, entries| {
+        for e in entries {
+            if show_origin {
+                writeln!(out, "{}\t{} = {}", e.origin, e.key, e.value)?;
+            } else {
+                writeln!(out, "{} = {}", e.key, e.value)?;
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// Outcome of a single `doctor` check
+#[derive(Debug, Serialize)]
+struct Check {
+    status: Status,
+    subject: String,
+    message: String,
+}
+
+impl Check {
+    fn new(status: Status, subject: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            subject: subject.into(),
+            message: message.into(),
+        }
+    }
+}
+
+fn doctor(json: bool) -> Result<ExitCode, Error> {
+    let mut checks = vec![];
+
+    if env::var_os(depth::VAR).is_some() {
+        checks.push(Check::new(
+            Status::Warning,
+            depth::VAR,
+            "set in the environment, autocc is running nested inside itself",
+        ));
+    }
+
+    for error in &config::get().errors {
+        checks.push(Check::new(
+            Status::Warning,
+            "config",
+            format!("{error}, ignoring"),
+        ));
+    }
+
+    // Tool variables, each checked once
+    let mut vars = Personality::all().map(|p| p.env_var()).collect::<Vec<_>>();
+    vars.sort();
+    vars.dedup();
+    for var in vars {
+        let Ok(value) = env::var(var) else {
+            continue;
+        };
+        let Some(words) = shell::split(&value) else {
+            checks.push(Check::new(Status::Error, var, "unterminated quoting"));
+            continue;
+        };
+        if words.iter().all(|w| launcher::is_launcher(w)) {
+            checks.push(Check::new(
+                Status::Warning,
+                var,
+                format!("{value:?} names no program and is ignored"),
+            ));
+        } else if Tool::from_env(var).is_none() {
+            checks.push(Check::new(
+                Status::Warning,
+                var,
+                format!("{value:?} resolves to autocc itself and is ignored"),
+            ));
+        }
+    }
+
+    if let Some(launcher) = launcher::from_env() {
+        if !search::is_executable(&launcher[0]) {
+            checks.push(Check::new(
+                Status::Warning,
+                "AUTOCC_LAUNCHER",
+                format!("{} is not executable", launcher[0]),
+            ));
+        }
+    }
+
+    for personality in Personality::all() {
+        let check = match Invocation::prepare(personality, []) {
+            Ok(invocation) => {
+                let resolution = Resolution::new(&invocation);
+                let tool = invocation.toolchain.tool();
+                let missing = tool
+                    .launcher
+                    .iter()
+                    .chain([&tool.program])
+                    .find(|p| !search::is_executable(p));
+
+                match missing {
+                    Some(program) => Check::new(
+                        Status::Error,
+                        personality.name(),
+                        format!("{program} ({}) is not executable", resolution.origin),
+                    ),
+                    None => Check::new(
+                        Status::Ok,
+                        personality.name(),
+                        format!(
+                            "{} ({}, {})",
+                            resolution.program, resolution.family, resolution.origin
+                        ),
+                    ),
+                }
+            }
+            Err(err) => Check::new(Status::Error, personality.name(), err.to_string()),
+        };
+        checks.push(check);
+    }
+
+    let failed = checks.iter().any(|c| c.status == Status::Error);
+
+    emit(json, &checks, |out, checks| {
+        for c in checks {
+            let status = match c.status {
+                Status::Ok => "ok",
+                Status::Warning => "warning",
+                Status::Error => "error",
+            };
+            writeln!(out, "{status:>7}  {}: {}", c.subject, c.message)?;
+        }
+        Ok(())
+    })?;
+
+    Ok(if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
@@ -0,0 +1,480 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Layered configuration files
+//!
+//! Serpent OS is stateless: defaults ship in `/usr/share/defaults/autocc`, and the
+//! administrator and user override them from `/etc/autocc` and `~/.config/autocc`.
+//! Every `*.toml` file in each directory is read in name order, later files overriding
+//! earlier ones key by key. A `.autocc.toml` found above the working directory layers
+//! over those for the project. Each setting stands in for an `AUTOCC_` variable, which
+//! overrides them all.
+
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use serde::Serialize;
+use toml::{Table, Value};
+
+use crate::personality::Personality;
+
+/// How a setting's value is spelled in its variable
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    String,
+    /// A list, joined with the separator
+    List(&'static str),
+    Bool,
+}
+
+/// A setting and the variable overriding it
+struct Setting {
+    key: &'static str,
+    var: &'static str,
+    kind: Kind,
+    /// Whether a project file may set it, leaving anything writing files or
+    /// skipping checks to the system and user
+    project: bool,
+}
+
+const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "prefer",
+        var: "AUTOCC_PREFER",
+        kind: Kind::List(","),
+        project: true,
+    },
+    Setting {
+        key: "toolchain",
+        var: "AUTOCC_TOOLCHAIN",
+        kind: Kind::String,
+        project: true,
+    },
+    Setting {
+        key: "clang_version",
+        var: "AUTOCC_CLANG_VERSION",
+        kind: Kind::String,
+        project: true,
+    },
+    Setting {
+        key: "gcc_version",
+        var: "AUTOCC_GCC_VERSION",
+        kind: Kind::String,
+        project: true,
+    },
+    Setting {
+        key: "sysroot",
+        var: "AUTOCC_SYSROOT",
+        kind: Kind::String,
+        project: true,
+    },
+    Setting {
+        key: "search_paths",
+        var: "AUTOCC_SEARCH_PATH",
+        kind: Kind::List(":"),
+        project: false,
+    },
+    Setting {
+        key: "launcher",
+        var: "AUTOCC_LAUNCHER",
+        kind: Kind::String,
+        project: true,
+    },
+    Setting {
+        key: "linker",
+        var: "AUTOCC_LINKER",
+        kind: Kind::String,
+        project: true,
+    },
+    Setting {
+        key: "emul32",
+        var: "AUTOCC_EMUL32",
+        kind: Kind::Bool,
+        project: false,
+    },
+    Setting {
+        key: "probe",
+        var: "AUTOCC_PROBE",
+        kind: Kind::Bool,
+        project: false,
+    },
+    Setting {
+        key: "cache",
+        var: "AUTOCC_CACHE",
+        kind: Kind::Bool,
+        project: false,
+    },
+    Setting {
+        key: "cache_dir",
+        var: "AUTOCC_CACHE_DIR",
+        kind: Kind::String,
+        project: false,
+    },
+    Setting {
+        key: "debug",
+        var: "AUTOCC_DEBUG",
+        kind: Kind::Bool,
+        project: false,
+    },
+    Setting {
+        key: "trace",
+        var: "AUTOCC_TRACE",
+        kind: Kind::String,
+        project: false,
+    },
+];
+
+/// Table of flags injected ahead of the caller's arguments, keyed by tool
+const FLAGS: &str = "flags";
+
+/// Table of per-target settings, keyed by triple
+const TARGET: &str = "target";
+
+/// Settings a target's table may hold
+const TARGET_KEYS: &[&str] = &["sysroot"];
+
+/// Name of the per-project file
+const PROJECT: &str = ".autocc.toml";
+
+/// Configuration directories, lowest precedence first
+pub fn dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![
+        PathBuf::from("/usr/share/defaults/autocc"),
+        PathBuf::from("/etc/autocc"),
+    ];
+
+    let user = env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(env::var_os("HOME")?).join(".config")));
+    dirs.extend(user.map(|d| d.join("autocc")));
+
+    dirs
+}
+
+/// Where a value came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    File(PathBuf),
+    Env(&'static str),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::File(path) => write!(f, "file:{}", path.display()),
+            Source::Env(var) => write!(f, "env:{var}"),
+        }
+    }
+}
+
+/// The merged configuration files
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Values by key (`prefer`, `flags.cc`), along with the file setting them
+    values: BTreeMap<String, (Value, PathBuf)>,
+    /// Files read, in order
+    pub files: Vec<PathBuf>,
+    /// The project file, if one was found
+    pub project: Option<PathBuf>,
+    /// Files or keys that were ignored, and why
+    pub errors: Vec<String>,
+}
+
+/// The configuration, read on first use
+///
+/// Reading can't trace, as the trace itself is configured here.
+pub fn get() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(Config::load)
+}
+
+/// The setting's value, from its variable or else the configuration files
+pub fn var(name: &str) -> Option<String> {
+    setting(name).map(|(value, _)| value)
+}
+
+/// A boolean setting, `default` when unset or not spelled as a boolean
+///
+/// `1`, `yes`, `true` and `on` turn it on and `0`, `no`, `false` and `off` turn it
+/// off, in any case.
+pub fn flag(name: &str, default: bool) -> bool {
+    match var(name).map(|v| v.to_ascii_lowercase()).as_deref() {
+        Some("1" | "yes" | "true" | "on") => true,
+        Some("0" | "no" | "false" | "off") => false,
+        _ => default,
+    }
+}
+
+/// The setting's value along with where it came from
+pub fn setting(name: &str) -> Option<(String, Source)> {
+    let setting = SETTINGS.iter().find(|s| s.var == name)?;
+    if let Ok(value) = env::var(setting.var) {
+        return Some((value, Source::Env(setting.var)));
+    }
+
+    let (value, path) = get().values.get(setting.key)?;
+    let value = match (setting.kind, value) {
+        (Kind::List(separator), Value::Array(items)) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(separator),
+        (Kind::Bool, Value::Boolean(b)) => if *b { "1" } else { "0" }.to_owned(),
+        (_, Value::String(s)) => s.clone(),
+        _ => return None,
+    };
+
+    Some((value, Source::File(path.clone())))
+}
+
+/// A setting from the target's table (`[target.aarch64-linux-gnu]`)
+pub fn target(triple: &str, key: &str) -> Option<(String, Source)> {
+    match get().values.get(&format!("{TARGET}.{triple}.{key}"))? {
+        (Value::String(value), path) => Some((value.clone(), Source::File(path.clone()))),
+        _ => None,
+    }
+}
+
+/// Flags configured for the tool
+pub fn flags(personality: Personality) -> Vec<String> {
+    let key = format!("{FLAGS}.{}", personality.name());
+    match get().values.get(&key) {
+        Some((Value::Array(flags), _)) => flags
+            .iter()
+            .filter_map(|f| f.as_str().map(str::to_owned))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// An effective value, for `autocc config`
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub origin: Source,
+}
+
+/// Every value in effect, variables overriding files
+pub fn entries() -> Vec<Entry> {
+    let config = get();
+    let mut entries = vec![];
+
+    for setting in SETTINGS {
+        if let Ok(value) = env::var(setting.var) {
+            entries.push(Entry {
+                key: setting.key.to_owned(),
+                value: Value::String(value),
+                origin: Source::Env(setting.var),
+            });
+        } else if let Some((value, path)) = config.values.get(setting.key) {
+            entries.push(Entry {
+                key: setting.key.to_owned(),
+                value: value.clone(),
+                origin: Source::File(path.clone()),
+            });
+        }
+    }
+
+    let tables = [format!("{FLAGS}."), format!("{TARGET}.")];
+    for (key, (value, path)) in &config.values {
+        if tables.iter().any(|t| key.starts_with(t)) {
+            entries.push(Entry {
+                key: key.clone(),
+                value: value.clone(),
+                origin: Source::File(path.clone()),
+            });
+        }
+    }
+
+    entries
+}
+
+impl Config {
+    fn load() -> Self {
+        let mut config = Self::default();
+
+        for dir in dirs() {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            let mut files = entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|e| e == "toml") && p.is_file())
+                .collect::<Vec<_>>();
+            files.sort();
+
+            for file in files {
+                config.read(&file, false);
+            }
+        }
+
+        config.project = project_file();
+        if let Some(project) = config.project.clone() {
+            config.read(&project, true);
+        }
+
+        config
+    }
+
+    /// Merge the file over what has been read so far
+    fn read(&mut self, path: &Path, project: bool) {
+        self.files.push(path.to_owned());
+
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) => {
+                self.errors.push(format!("{}: {err}", path.display()));
+                return;
+            }
+        };
+        let table = match content.parse::<Table>() {
+            Ok(table) => table,
+            Err(err) => {
+                let line = err
+                    .span()
+                    .map_or(1, |s| content[..s.start].matches('\n').count() + 1);
+                self.errors
+                    .push(format!("{}:{line}: {}", path.display(), err.message()));
+                return;
+            }
+        };
+
+        for (key, value) in table {
+            if key == FLAGS {
+                self.read_flags(path, value);
+                continue;
+            }
+            if key == TARGET {
+                self.read_targets(path, value);
+                continue;
+            }
+
+            let Some(setting) = SETTINGS.iter().find(|s| s.key == key) else {
+                self.errors
+                    .push(format!("{}: unknown key {key}", path.display()));
+                continue;
+            };
+            if project && !setting.project {
+                self.errors.push(format!(
+                    "{}: {key} can't be set per project",
+                    path.display()
+                ));
+                continue;
+            }
+            let valid = match setting.kind {
+                Kind::String => value.is_str(),
+                Kind::List(_) => value
+                    .as_array()
+                    .is_some_and(|a| a.iter().all(Value::is_str)),
+                Kind::Bool => value.is_bool(),
+            };
+            if !valid {
+                self.errors.push(format!(
+                    "{}: {key} must be {}",
+                    path.display(),
+                    match setting.kind {
+                        Kind::String => "a string",
+                        Kind::List(_) => "a list of strings",
+                        Kind::Bool => "true or false",
+                    }
+                ));
+                continue;
+            }
+
+            self.values.insert(key, (value, path.to_owned()));
+        }
+    }
+
+    /// Merge the `[flags]` table, one key per tool
+    fn read_flags(&mut self, path: &Path, value: Value) {
+        let Value::Table(tools) = value else {
+            self.errors
+                .push(format!("{}: {FLAGS} must be a table", path.display()));
+            return;
+        };
+
+        for (tool, flags) in tools {
+            if Personality::from_name(&tool).is_none() {
+                self.errors
+                    .push(format!("{}: unknown tool {FLAGS}.{tool}", path.display()));
+            } else if !flags
+                .as_array()
+                .is_some_and(|a| a.iter().all(Value::is_str))
+            {
+                self.errors.push(format!(
+                    "{}: {FLAGS}.{tool} must be a list of strings",
+                    path.display()
+                ));
+            } else {
+                self.values
+                    .insert(format!("{FLAGS}.{tool}"), (flags, path.to_owned()));
+            }
+        }
+    }
+
+    /// Merge the `[target]` table, one table of settings per triple
+    fn read_targets(&mut self, path: &Path, value: Value) {
+        let Value::Table(targets) = value else {
+            self.errors
+                .push(format!("{}: {TARGET} must be a table", path.display()));
+            return;
+        };
+
+        for (triple, settings) in targets {
+            let Value::Table(settings) = settings else {
+                self.errors.push(format!(
+                    "{}: {TARGET}.{triple} must be a table",
+                    path.display()
+                ));
+                continue;
+            };
+
+            for (key, value) in settings {
+                let name = format!("{TARGET}.{triple}.{key}");
+                if !TARGET_KEYS.contains(&key.as_str()) {
+                    self.errors
+                        .push(format!("{}: unknown key {name}", path.display()));
+                } else if !value.is_str() {
+                    self.errors
+                        .push(format!("{}: {name} must be a string", path.display()));
+                } else {
+                    self.values.insert(name, (value, path.to_owned()));
+                }
+            }
+        }
+    }
+}
+
+/// Find the project file in the working directory or above, stopping at the top of a
+/// repository or at a filesystem boundary
+fn project_file() -> Option<PathBuf> {
+    let mut dir = env::current_dir().ok()?;
+    let mut dev = fs::metadata(&dir).ok()?.dev();
+
+    loop {
+        let file = dir.join(PROJECT);
+        if file.is_file() {
+            return Some(file);
+        }
+        if dir.join(".git").exists() {
+            return None;
+        }
+
+        let parent = dir.parent()?.to_owned();
+        let parent_dev = fs::metadata(&parent).ok()?.dev();
+        if parent_dev != dev {
+            return None;
+        }
+        (dir, dev) = (parent, parent_dev);
+    }
+}
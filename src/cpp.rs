@@ -0,0 +1,126 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! `cpp` (and `/lib/cpp`) personality
+//!
+//! GNU `cpp` is happy to preprocess anything it's handed, reads stdin when given
+//! no inputs and treats a second operand as the output file. `clang -E` does none
+//! of that, so legacy users (imake, xrdb, ...) need their arguments massaging.
+
+use std::ffi::OsString;
+
+/// Options taking their value as the following argument
+const OPTIONS_WITH_VALUE: &[&str] = &[
+    "-o",
+    "-I",
+    "-D",
+    "-U",
+    "-A",
+    "-x",
+    "-include",
+    "-imacros",
+    "-isystem",
+    "-idirafter",
+    "-iquote",
+    "-iprefix",
+    "-iwithprefix",
+    "-iwithprefixbefore",
+    "-isysroot",
+    "--sysroot",
+    "-MF",
+    "-MT",
+    "-MQ",
+    "-Xpreprocessor",
+];
+
+/// Rewrite GNU `cpp` arguments into an equivalent `clang -E` invocation
+pub fn clang_args(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut result = vec![OsString::from("-E")];
+    let mut operands = vec![];
+    let mut has_language = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.to_str() else {
+            operands.push(arg);
+            continue;
+        };
+
+        match flag {
+            // clang only knows the long spelling
+            "-traditional" | "-traditional-cpp" => result.push("-traditional-cpp".into()),
+            "-" => operands.push(arg),
+            x if OPTIONS_WITH_VALUE.contains(&x) => {
+                has_language |= x == "-x";
+                result.push(arg);
+                result.extend(args.next());
+            }
+            x if x.starts_with('-') => {
+                has_language |= x.starts_with("-x");
+                result.push(arg);
+            }
+            _ => operands.push(arg),
+        }
+    }
+
+    // Anything we're handed is C, regardless of extension (Imakefile, .Xresources)
+    if !has_language {
+        result.splice(1..1, ["-x".into(), "c".into()]);
+    }
+
+    let mut operands = operands.into_iter();
+    // No input means stdin
+    result.push(operands.next().unwrap_or_else(|| "-".into()));
+    // A second operand is the output file
+    if let Some(output) = operands.next() {
+        result.push("-o".into());
+        result.push(output);
+    }
+    result.extend(operands);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::clang_args;
+
+    fn translate(args: &[&str]) -> Vec<OsString> {
+        clang_args(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn second_operand_is_output() {
+        assert_eq!(
+            translate(&["-DFOO", "in.h", "out.i"]),
+            ["-E", "-x", "c", "-DFOO", "in.h", "-o", "out.i"]
+        );
+    }
+
+    #[test]
+    fn stdin_without_operands() {
+        assert_eq!(
+            translate(&["-I", "inc"]),
+            ["-E", "-x", "c", "-I", "inc", "-"]
+        );
+    }
+
+    #[test]
+    fn traditional() {
+        assert_eq!(
+            translate(&["-traditional", "Imakefile"]),
+            ["-E", "-x", "c", "-traditional-cpp", "Imakefile"]
+        );
+    }
+
+    #[test]
+    fn explicit_language_kept() {
+        assert_eq!(
+            translate(&["-x", "assembler-with-cpp", "in.S"]),
+            ["-E", "-x", "assembler-with-cpp", "in.S"]
+        );
+    }
+}
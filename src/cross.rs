@@ -0,0 +1,295 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Cross compilation targets
+//!
+//! The target is the triple prefixed to the name we were invoked with
+//! (`aarch64-linux-gnu-cc`), or else `CHOST` or `CROSS_COMPILE`. It's then served by
+//! a `<triple>-gcc` style compiler if one is installed, or clang retargeted with
+//! `--target`.
+
+use std::{env, fmt, path::Path, sync::OnceLock};
+
+use crate::{personality::Personality, trace::trace};
+
+/// Where the target came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// Prefixed to the name we were invoked with
+    Name,
+
+    /// An environment variable
+    Env(&'static str),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Name => write!(f, "from the invocation name"),
+            Origin::Env(var) => write!(f, "from {var}"),
+        }
+    }
+}
+
+/// The target we're compiling for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub triple: String,
+    pub origin: Origin,
+}
+
+impl Target {
+    /// The tool's name for this target (`aarch64-linux-gnu-gcc`)
+    pub fn prefixed(&self, tool: &str) -> String {
+        format!("{}-{tool}", self.triple)
+    }
+
+    /// Whether the target is the machine we're running on
+    pub fn is_native(&self) -> bool {
+        is_host(&self.triple)
+    }
+}
+
+static TARGET: OnceLock<Option<Target>> = OnceLock::new();
+
+/// Architectures starting a triple, less their variants (`armv7l`, `powerpc64le`)
+const ARCHITECTURES: &[&str] = &[
+    "aarch64",
+    "alpha",
+    "amdgcn",
+    "arm",
+    "avr",
+    "bpf",
+    "csky",
+    "hexagon",
+    "hppa",
+    "i386",
+    "i486",
+    "i586",
+    "i686",
+    "ia64",
+    "loongarch",
+    "m68k",
+    "microblaze",
+    "mips",
+    "msp430",
+    "nios2",
+    "nvptx",
+    "or1k",
+    "powerpc",
+    "ppc",
+    "riscv",
+    "s390",
+    "sparc",
+    "thumb",
+    "wasm",
+    "x86_64",
+    "xtensa",
+];
+
+/// Systems named later in a triple, less their versions (`freebsd14`)
+const SYSTEMS: &[&str] = &[
+    "linux",
+    "none",
+    "elf",
+    "eabi",
+    "mingw32",
+    "windows",
+    "cygwin",
+    "darwin",
+    "freebsd",
+    "netbsd",
+    "openbsd",
+    "dragonfly",
+    "solaris",
+    "android",
+    "wasi",
+    "haiku",
+    "hurd",
+];
+
+/// Whether the string looks like a triple (`aarch64-linux-gnu`, `riscv64-serpent-linux`)
+///
+/// A known architecture or system is required, so a wrapper such as `my-wrapper-cc`
+/// isn't taken for a cross tool.
+fn is_triple(s: &str) -> bool {
+    let parts = s.split('-').collect::<Vec<_>>();
+    let known = |part: &str, names: &[&str]| names.iter().any(|n| part.starts_with(n));
+
+    parts.len() >= 2
+        && parts[0].starts_with(|c: char| c.is_ascii_alphabetic())
+        && parts.iter().all(|p| {
+            !p.is_empty()
+                && p.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        })
+        && (known(parts[0], ARCHITECTURES) || parts[1..].iter().any(|p| known(p, SYSTEMS)))
+}
+
+/// The host's environment and ABI as triples name them (`gnu`, `gnux32`, `musleabihf`)
+fn host_env() -> String {
+    let env = if cfg!(target_env = "musl") {
+        "musl"
+    } else if cfg!(target_env = "gnu") {
+        "gnu"
+    } else {
+        ""
+    };
+    let abi = if cfg!(target_abi = "x32") {
+        "x32"
+    } else if cfg!(target_abi = "eabihf") {
+        "eabihf"
+    } else if cfg!(target_abi = "eabi") {
+        "eabi"
+    } else {
+        ""
+    };
+
+    format!("{env}{abi}")
+}
+
+/// Whether the triple names the host's architecture, OS and environment, which may
+/// be left out (`x86_64-serpent-linux`)
+fn is_host(triple: &str) -> bool {
+    let parts = triple.split('-').collect::<Vec<_>>();
+    let Some(os) = parts
+        .iter()
+        .skip(1)
+        .position(|p| p.starts_with(env::consts::OS))
+    else {
+        return false;
+    };
+
+    parts[0] == env::consts::ARCH
+        && parts
+            .get(os + 2)
+            .is_none_or(|environment| *environment == host_env())
+}
+
+/// Split a triple prefixed tool name (`aarch64-linux-gnu-c++`) into the triple and
+/// the tool
+pub fn split_name(name: &str) -> Option<(&str, &str)> {
+    name.match_indices('-')
+        .map(|(i, _)| (&name[..i], &name[i + 1..]))
+        .find(|(triple, tool)| Personality::from_name(tool).is_some() && is_triple(triple))
+}
+
+/// Record the target for the name we were invoked with, before anything resolves
+///
+/// The build machine's compiler always targets the host.
+pub fn init(name: &str, personality: Personality) {
+    let target = match personality {
+        Personality::Build(_) => None,
+        _ => select(name),
+    };
+    let _ = TARGET.set(target);
+}
+
+/// The target being compiled for, or `None` for the host
+pub fn target() -> Option<&'static Target> {
+    TARGET.get_or_init(|| select("")).as_ref()
+}
+
+/// The target the personality compiles for, the build machine's compiler always
+/// targets the host
+pub fn target_for(personality: Personality) -> Option<&'static Target> {
+    match personality {
+        Personality::Build(_) => None,
+        _ => target(),
+    }
+}
+
+/// Find the target from the name, or else the environment
+fn select(name: &str) -> Option<Target> {
+    let name = Path::new(name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    if let Some((triple, _)) = split_name(name) {
+        return Some(Target {
+            triple: triple.to_owned(),
+            origin: Origin::Name,
+        });
+    }
+
+    from_env("CHOST").or_else(|| from_env("CROSS_COMPILE"))
+}
+
+/// The target named by `CHOST`, or the prefix in `CROSS_COMPILE` (`aarch64-linux-gnu-`)
+fn from_env(var: &'static str) -> Option<Target> {
+    let value = env::var(var).ok()?;
+    let prefix = Path::new(&value).file_name()?.to_str()?;
+    let triple = match var {
+        "CROSS_COMPILE" => prefix.strip_suffix('-')?,
+        _ => prefix,
+    };
+    if !is_triple(triple) {
+        trace!("{var} = {value:?} is not a target triple, ignoring");
+        return None;
+    }
+
+    // Build environments often set CHOST for native builds
+    if var == "CHOST" && is_host(triple) {
+        trace!("{var} = {value:?} names the host, ignoring");
+        return None;
+    }
+
+    trace!("{var} = {value:?}, targeting {triple}");
+    Some(Target {
+        triple: triple.to_owned(),
+        origin: Origin::Env(var),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::{host_env, is_host, is_triple, split_name};
+
+    #[test]
+    fn triples() {
+        assert!(is_triple("aarch64-linux-gnu"));
+        assert!(is_triple("riscv64-serpent-linux"));
+        assert!(is_triple("x86_64-w64-mingw32"));
+        assert!(!is_triple("build"));
+        assert!(!is_triple("aarch64--gnu"));
+        assert!(!is_triple("1abc-linux"));
+        assert!(is_triple("arm-none-eabi"));
+        assert!(is_triple("armv7l-unknown-linux-gnueabihf"));
+        assert!(is_triple("custom-serpent-linux"));
+        assert!(!is_triple("my-wrapper"));
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(
+            split_name("aarch64-linux-gnu-c++"),
+            Some(("aarch64-linux-gnu", "c++"))
+        );
+        assert_eq!(
+            split_name("riscv64-serpent-linux-build-cc"),
+            Some(("riscv64-serpent-linux", "build-cc"))
+        );
+        assert_eq!(split_name("build-cc"), None);
+        assert_eq!(split_name("build-c++"), None);
+        assert_eq!(split_name("aarch64-linux-gnu-gcc"), None);
+        assert_eq!(split_name("my-wrapper-cc"), None);
+    }
+
+    #[test]
+    fn host() {
+        let (arch, os) = (env::consts::ARCH, env::consts::OS);
+        assert!(is_host(&format!("{arch}-unknown-{os}-{}", host_env())));
+        assert!(is_host(&format!("{arch}-serpent-{os}")));
+        assert!(!is_host(&format!("{arch}-w64-mingw32")));
+        assert!(!is_host("sparc64-unknown-netbsd"));
+        // Another libc or ABI on the same machine is a cross target
+        assert_eq!(is_host(&format!("{arch}-{os}-musl")), host_env() == "musl");
+        assert_eq!(
+            is_host(&format!("{arch}-{os}-gnux32")),
+            host_env() == "gnux32"
+        );
+    }
+}
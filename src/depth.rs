@@ -0,0 +1,50 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Nesting depth of autocc invocations
+//!
+//! Legitimate nesting is shallow (`cc` -> `gcc` -> `as` -> ...), whereas a loop we
+//! couldn't spot on the filesystem (a wrapper script calling `cc`) nests forever.
+//! Each exec carries the depth in the environment so a loop aborts instead of
+//! hanging the build.
+
+use std::{env, fmt};
+
+/// Environment variable carrying the depth to child processes
+pub const VAR: &str = "AUTOCC_DEPTH";
+
+/// Deepest nesting we tolerate
+const LIMIT: u32 = 8;
+
+/// We've been invoked through ourselves too many times
+#[derive(Debug)]
+pub struct TooDeep {
+    depth: u32,
+}
+
+impl fmt::Display for TooDeep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "autocc invoked itself {} levels deep, check that CC, LD and PATH don't lead back to autocc",
+            self.depth
+        )
+    }
+}
+
+impl std::error::Error for TooDeep {}
+
+/// Enter a new level, returning the depth to hand to the child
+pub fn enter() -> Result<u32, TooDeep> {
+    let depth = env::var(VAR)
+        .ok()
+        .and_then(|d| d.parse::<u32>().ok())
+        .unwrap_or_default();
+
+    if depth >= LIMIT {
+        Err(TooDeep { depth })
+    } else {
+        Ok(depth + 1)
+    }
+}
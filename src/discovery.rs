@@ -0,0 +1,248 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Enumerate every compiler installed on the system
+//!
+//! Beyond `PATH` we look where distributions park additional releases, and accept
+//! versioned names (`clang-18`, `gcc-13`) alongside the bare drivers.
+
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    config,
+    personality::Language,
+    probe, search,
+    toolchain::{Family, Origin, Tool, Toolchain},
+    trace::trace,
+    version::Version,
+};
+
+/// Parent directories whose children may each hold a `bin` directory of compilers
+const PREFIXES: &[(&str, &str)] = &[("/usr/lib", "llvm-"), ("/opt", "")];
+
+/// A compiler found on the system
+#[derive(Debug)]
+pub struct Installed {
+    pub toolchain: Toolchain,
+    /// Version carried in the binary name (`clang-18`)
+    pub suffix: Option<Version>,
+}
+
+/// Directories to search, `PATH` first, then `AUTOCC_SEARCH_PATH`
+pub fn search_dirs() -> Vec<PathBuf> {
+    let path = env::var_os("PATH").unwrap_or_else(|| "/usr/local/bin:/usr/bin:/bin".into());
+    let mut dirs = env::split_paths(&path).collect::<Vec<_>>();
+    if let Some(extra) = config::var("AUTOCC_SEARCH_PATH") {
+        dirs.extend(env::split_paths(&extra).filter(|d| !d.as_os_str().is_empty()));
+    }
+
+    for (parent, prefix) in PREFIXES {
+        let Ok(entries) = fs::read_dir(parent) else {
+            continue;
+        };
+        let mut children = entries
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().starts_with(prefix))
+            .map(|e| e.path().join("bin"))
+            .filter(|p| p.is_dir())
+            .collect::<Vec<_>>();
+        children.sort();
+        dirs.extend(children);
+    }
+
+    dirs
+}
+
+/// Split a binary name into the driver's version suffix: `Some(None)` for the bare
+/// driver, `Some(Some(18))` for `clang-18`, `None` for anything else
+pub fn driver_version(name: &str, driver: &str) -> Option<Option<Version>> {
+    let rest = name.strip_prefix(driver)?;
+    if rest.is_empty() {
+        return Some(None);
+    }
+
+    rest.strip_prefix('-')?.parse().ok().map(Some)
+}
+
+/// Every installed compiler for the language, LLVM first, deduplicated by identity
+pub fn installed(language: Language) -> Vec<Installed> {
+    let dirs = search_dirs();
+    let mut seen = HashSet::new();
+    let mut found = vec![];
+
+    let families = [
+        (language.llvm_driver(), Family::LLVM),
+        (language.gnu_driver(), Family::GNU),
+    ];
+    for (driver, family) in families {
+        for dir in &dirs {
+            for (path, suffix) in drivers_in(dir, driver) {
+                if search::is_self(&path) || !seen.insert(fs::canonicalize(&path).ok()) {
+                    continue;
+                }
+                found.push(Installed {
+                    toolchain: family.toolchain(Tool::new(path.to_string_lossy())),
+                    suffix,
+                });
+            }
+        }
+    }
+
+    found
+}
+
+/// Acceptable versions of a driver, from `AUTOCC_CLANG_VERSION` or `AUTOCC_GCC_VERSION`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Requirement {
+    /// This release or a more specific form of it (`18` accepts `18.1.8`)
+    Exactly(Version),
+
+    /// This release or anything newer (`>=13`)
+    AtLeast(Version),
+}
+
+impl Requirement {
+    /// Parse the requirement from the variable or its setting, if set
+    pub fn from_env(var: &str) -> Option<Self> {
+        let value = config::var(var)?;
+        match value.parse() {
+            Ok(requirement) => {
+                trace!("{var} = {value:?}");
+                Some(requirement)
+            }
+            Err(()) => {
+                trace!("{var} = {value:?} is not a version, ignoring");
+                None
+            }
+        }
+    }
+
+    pub fn accepts(&self, version: &Version) -> bool {
+        match self {
+            Requirement::Exactly(v) => version.matches(v),
+            Requirement::AtLeast(v) => version >= v,
+        }
+    }
+}
+
+impl FromStr for Requirement {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().strip_prefix(">=") {
+            Some(minimum) => Ok(Requirement::AtLeast(minimum.parse()?)),
+            None => Ok(Requirement::Exactly(s.parse()?)),
+        }
+    }
+}
+
+/// Find the driver to use for a family
+///
+/// Without a requirement the bare driver in `PATH` is used as the distribution's
+/// default, falling back to the newest versioned or out-of-`PATH` release. With one,
+/// the newest release satisfying it is picked, asking each candidate its version.
+pub fn select(driver: &str, var: &'static str) -> Option<Tool> {
+    let requirement = Requirement::from_env(var);
+
+    if requirement.is_none() {
+        if let Some(program) = search::find_in_path(driver) {
+            return Some(Tool::new(program));
+        }
+    }
+
+    trace!("looking for versioned {driver}");
+    let dirs = search_dirs();
+    let mut seen = HashSet::new();
+    let mut best: Option<(Version, PathBuf)> = None;
+
+    for dir in &dirs {
+        for (path, suffix) in drivers_in(dir, driver) {
+            if search::is_self(&path) || !seen.insert(fs::canonicalize(&path).ok()) {
+                continue;
+            }
+            // The suffix is enough to rank releases, a requirement may need more precision
+            let version = match (&requirement, suffix) {
+                (None, Some(suffix)) => Some(suffix),
+                _ => probe::version(&path.to_string_lossy()),
+            };
+            let Some(version) = version else {
+                trace!("  {} has an unknown version, skipping", path.display());
+                continue;
+            };
+            if requirement.as_ref().is_some_and(|r| !r.accepts(&version)) {
+                trace!("  {} is {version}, rejected by {var}", path.display());
+                continue;
+            }
+            trace!("  {} is {version}", path.display());
+            // Earlier directories win ties
+            if best.as_ref().is_none_or(|(v, _)| version > *v) {
+                best = Some((version, path));
+            }
+        }
+    }
+
+    let (version, path) = best?;
+    trace!("  selected {} ({version})", path.display());
+    let tool = Tool::new(path.to_string_lossy());
+    Some(match requirement {
+        Some(_) => tool.with_origin(Origin::Version(var.into())),
+        None => tool,
+    })
+}
+
+/// Bare and versioned drivers in the directory, sorted by name
+fn drivers_in(dir: &Path, driver: &str) -> Vec<(PathBuf, Option<Version>)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut drivers = entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name();
+            let suffix = driver_version(name.to_str()?, driver)?;
+            Some((e.path(), suffix))
+        })
+        .collect::<Vec<_>>();
+    drivers.sort();
+
+    drivers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Requirement;
+
+    fn accepts(requirement: &str, version: &str) -> bool {
+        let requirement = requirement.parse::<Requirement>().unwrap();
+        requirement.accepts(&version.parse().unwrap())
+    }
+
+    #[test]
+    fn exactly() {
+        assert!(accepts("18", "18.1.8"));
+        assert!(accepts("18.1", "18.1.8"));
+        assert!(!accepts("18", "17.0.6"));
+        assert!(!accepts("18.1", "18.0.1"));
+    }
+
+    #[test]
+    fn at_least() {
+        assert!(accepts(">=13", "13.2.0"));
+        assert!(accepts(">=13", "18.1.8"));
+        assert!(accepts(" >=13", "13"));
+        assert!(!accepts(">=13", "12.2.0"));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!("latest".parse::<Requirement>().is_err());
+        assert!(">=".parse::<Requirement>().is_err());
+    }
+}
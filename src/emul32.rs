@@ -0,0 +1,215 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! 32-bit (emul32) builds on x86_64
+//!
+//! Serpent's emul32 builds pass `-m32` (or `-mx32`) to the native compiler, which only
+//! works when GCC was built with multilib support. Otherwise the build is handed to an
+//! i686 compiler (`i686-linux-gnu-gcc`), or clang targeting i686, before it fails late
+//! at link time. `AUTOCC_EMUL32=1` asks for `-m32` without touching the build's flags.
+
+use std::{env, ffi::OsString};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache, config, cross, discovery,
+    error::Error,
+    personality::{Language, Personality},
+    probe, search,
+    toolchain::{Family, Tool, Toolchain},
+    trace::trace,
+};
+
+/// The 32-bit ABIs of x86_64
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Abi {
+    /// i686
+    M32,
+
+    /// 32-bit pointers on x86_64
+    X32,
+}
+
+impl Abi {
+    /// The driver flag selecting it
+    pub fn flag(&self) -> &'static str {
+        match self {
+            Abi::M32 => "-m32",
+            Abi::X32 => "-mx32",
+        }
+    }
+
+    /// The triple for the ABI on the x86_64 host (`x86_64-linux-gnu`)
+    fn triple(&self, host: &str) -> String {
+        let (_, rest) = host.split_once('-').unwrap_or(("", "linux-gnu"));
+        match (self, rest.rsplit_once('-')) {
+            (Abi::M32, _) => format!("i686-{rest}"),
+            // x32 is an environment (`linux-gnux32`), added when the host names none
+            (Abi::X32, Some((system, env @ ("gnu" | "musl")))) => {
+                format!("x86_64-{system}-{env}x32")
+            }
+            (Abi::X32, _) => format!("x86_64-{rest}-gnux32"),
+        }
+    }
+}
+
+/// Whether `AUTOCC_EMUL32` asks for `-m32`
+fn enabled() -> bool {
+    config::flag("AUTOCC_EMUL32", false)
+}
+
+/// The ABI requested by the last of `-m32`, `-mx32` and `-m64`, or else
+/// `AUTOCC_EMUL32`, along with whether the flag must be added
+pub fn requested(personality: Personality, args: &[OsString]) -> Option<(Abi, bool)> {
+    let last = args.iter().rev().find_map(|arg| match arg.to_str()? {
+        "-m32" => Some(Some(Abi::M32)),
+        "-mx32" => Some(Some(Abi::X32)),
+        "-m64" => Some(None),
+        _ => None,
+    });
+
+    match last {
+        Some(abi) => abi.map(|abi| (abi, false)),
+        // The build machine's compiler isn't part of the emul32 build
+        None if enabled() && !matches!(personality, Personality::Build(_)) => {
+            Some((Abi::M32, true))
+        }
+        None => None,
+    }
+}
+
+/// What GCC says about its ABIs, cached as every compile of an emul32 build asks
+#[derive(Debug, Serialize, Deserialize)]
+struct Multilib {
+    /// Default target (`x86_64-linux-gnu`)
+    target: Option<String>,
+    /// Option sets with libraries (`-m32`, `-mx32`)
+    options: Vec<String>,
+}
+
+impl Multilib {
+    fn probe(gcc: &Tool) -> Self {
+        Self {
+            target: probe::target(&gcc.program),
+            options: probe::multilibs(gcc),
+        }
+    }
+}
+
+/// Check the compiler can build for the ABI, otherwise route to one that can
+pub fn route(personality: Personality, toolchain: Toolchain, abi: Abi) -> Result<Toolchain, Error> {
+    let language = personality.language();
+    let tool = toolchain.tool();
+    // Known without running anything: clang and GCC are native unless cross compiling
+    let target = tool
+        .identity
+        .as_ref()
+        .and_then(|i| i.target.clone())
+        .or_else(|| cross::target_for(personality).map(|t| t.triple.clone()));
+    let arch = target.as_deref().map_or(env::consts::ARCH, |t| {
+        t.split('-').next().unwrap_or_default()
+    });
+
+    // Other architectures give the flags their own meaning
+    if !matches!(arch, "x86_64" | "i386" | "i486" | "i586" | "i686") {
+        trace!(
+            "{} targets {arch}, not routing {}",
+            tool.program,
+            abi.flag()
+        );
+        return Ok(toolchain);
+    }
+
+    let multilib = match &toolchain {
+        // clang's x86 backend handles both ABIs itself
+        Toolchain::LLVM(_) => None,
+        Toolchain::GNU(_) if arch != "x86_64" && abi == Abi::M32 => None,
+        _ => Some(cache::get_or_probe("multilib", tool, Multilib::probe)),
+    };
+    let Some(multilib) = multilib.filter(|m| !m.options.iter().any(|o| o == abi.flag())) else {
+        trace!("{} supports {}", tool.program, abi.flag());
+        return Ok(toolchain);
+    };
+
+    let host = multilib
+        .target
+        .or_else(|| target.clone())
+        .unwrap_or_else(|| format!("{arch}-linux-gnu"));
+    let triple = abi.triple(&host);
+    trace!(
+        "{} lacks {} multilib, looking for a {triple} compiler",
+        tool.program,
+        abi.flag()
+    );
+
+    let mut routed = cross_compiler(language, &triple)
+        .or_else(|| {
+            let clang = discovery::select(language.llvm_driver(), "AUTOCC_CLANG_VERSION")?;
+            Some(Toolchain::LLVM(
+                clang.with_args([format!("--target={triple}")]),
+            ))
+        })
+        .ok_or_else(|| Error::Emul32 {
+            flag: abi.flag(),
+            program: tool.program.clone(),
+            compiler: format!("{triple}-{}", language.gnu_driver()),
+        })?;
+
+    trace!("routing {} to {}", abi.flag(), routed.tool().program);
+    // Keep whatever the build asked for through CC
+    let routed_tool = routed.tool_mut();
+    routed_tool.launcher.clone_from(&tool.launcher);
+    routed_tool.args.splice(0..0, tool.args.iter().cloned());
+    Ok(routed)
+}
+
+/// A compiler built for the triple (`i686-linux-gnu-gcc`)
+fn cross_compiler(language: Language, triple: &str) -> Option<Toolchain> {
+    [Family::GNU, Family::LLVM].into_iter().find_map(|family| {
+        let driver = format!("{triple}-{}", family.driver(language)?);
+        Some(family.toolchain(Tool::new(search::find_in_path(driver)?)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::{requested, Abi};
+    use crate::personality::{Language, Personality};
+
+    #[test]
+    fn triples() {
+        assert_eq!(Abi::M32.triple("x86_64-linux-gnu"), "i686-linux-gnu");
+        assert_eq!(
+            Abi::M32.triple("x86_64-serpent-linux"),
+            "i686-serpent-linux"
+        );
+        assert_eq!(Abi::X32.triple("x86_64-linux-gnu"), "x86_64-linux-gnux32");
+        assert_eq!(
+            Abi::X32.triple("x86_64-pc-linux-musl"),
+            "x86_64-pc-linux-muslx32"
+        );
+        assert_eq!(
+            Abi::X32.triple("x86_64-serpent-linux"),
+            "x86_64-serpent-linux-gnux32"
+        );
+    }
+
+    fn abi(args: &[&str]) -> Option<(Abi, bool)> {
+        let args = args.iter().map(OsString::from).collect::<Vec<_>>();
+        requested(Personality::Build(Language::C), &args)
+    }
+
+    #[test]
+    fn last_flag_wins() {
+        assert_eq!(abi(&["-m32", "-c", "x.c"]), Some((Abi::M32, false)));
+        assert_eq!(abi(&["-m32", "-mx32"]), Some((Abi::X32, false)));
+        assert_eq!(abi(&["-mx32", "-m32"]), Some((Abi::M32, false)));
+        assert_eq!(abi(&["-m32", "-m64"]), None);
+        assert_eq!(abi(&["-m64", "-m32"]), Some((Abi::M32, false)));
+        assert_eq!(abi(&["-c", "x.c"]), None);
+    }
+}
@@ -0,0 +1,149 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Failures and the exit codes they map to
+//!
+//! We stand in for tools invoked by configure scripts, which decide what works by
+//! exit status alone, so a failure must never look like success.
+
+use std::{fmt, io};
+
+use crate::{
+    config::{self, Source},
+    cross,
+    depth::TooDeep,
+    personality::Personality,
+    posix::ConflictingStandard,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    /// Nothing suitable to run for the personality, or nothing satisfying the
+    /// variable requesting it
+    NotFound(Personality, Option<&'static str>),
+
+    /// The resolved program couldn't be executed
+    Exec {
+        program: String,
+        origin: String,
+        source: io::Error,
+    },
+
+    /// POSIX utility asked for a different standard
+    Standard(ConflictingStandard),
+
+    /// The configured sysroot doesn't exist
+    Sysroot { path: String, origin: Source },
+
+    /// Nothing installed can build for the 32-bit ABI
+    Emul32 {
+        flag: &'static str,
+        program: String,
+        compiler: String,
+    },
+
+    /// Exec loop back into autocc
+    Recursion(TooDeep),
+
+    /// The management interface couldn't write its output
+    Output(io::Error),
+
+    /// Bad command line for the management interface
+    Usage(String),
+}
+
+impl Error {
+    /// Exit status, following shell conventions for exec failures
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::NotFound(..) => 127,
+            Error::Exec { source, .. } if source.kind() == io::ErrorKind::NotFound => 127,
+            Error::Exec { .. } => 126,
+            Error::Output(_)
+            | Error::Standard(_)
+            | Error::Sysroot { .. }
+            | Error::Emul32 { .. }
+            | Error::Recursion(_) => 1,
+            Error::Usage(_) => 2,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(personality, Some(var)) => write!(
+                f,
+                "no {} found satisfying {var}={:?}",
+                match cross::target_for(*personality) {
+                    Some(target) => target.prefixed(personality.name()),
+                    None => personality.name().to_owned(),
+                },
+                config::var(var).unwrap_or_default()
+            ),
+            Error::NotFound(personality, None) => match cross::target_for(*personality) {
+                Some(target) => write!(
+                    f,
+                    "no {} found, set {} or install clang or {}",
+                    target.prefixed(personality.name()),
+                    personality.env_var(),
+                    target.prefixed(personality.language().gnu_driver())
+                ),
+                None => write!(
+                    f,
+                    "no {} found, set {} or install clang or {}",
+                    personality.name(),
+                    personality.env_var(),
+                    personality.language().gnu_driver()
+                ),
+            },
+            Error::Exec {
+                program,
+                origin,
+                source,
+            } => write!(f, "failed to execute {program} ({origin}): {source}"),
+            Error::Standard(err) => write!(f, "{err}"),
+            Error::Sysroot { path, origin } => {
+                write!(f, "sysroot {path} ({origin}) is not a directory")
+            }
+            Error::Emul32 {
+                flag,
+                program,
+                compiler,
+            } => write!(
+                f,
+                "{program} has no {flag} multilib support, install {compiler} or clang"
+            ),
+            Error::Recursion(err) => write!(f, "{err}"),
+            Error::Output(err) => write!(f, "failed to write output: {err}"),
+            Error::Usage(msg) => write!(f, "{msg} (see `autocc --help`)"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Exec { source, .. } | Error::Output(source) => Some(source),
+            Error::Standard(err) => Some(err),
+            Error::Recursion(err) => Some(err),
+            Error::NotFound(..)
+            | Error::Sysroot { .. }
+            | Error::Emul32 { .. }
+            | Error::Usage(_) => None,
+        }
+    }
+}
+
+impl From<ConflictingStandard> for Error {
+    fn from(err: ConflictingStandard) -> Self {
+        Error::Standard(err)
+    }
+}
+
+impl From<TooDeep> for Error {
+    fn from(err: TooDeep) -> Self {
+        Error::Recursion(err)
+    }
+}
@@ -0,0 +1,152 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! The command line handed over to the real tool
+
+use std::{
+    ffi::OsString,
+    os::unix::process::CommandExt,
+    process::{self, Command},
+};
+
+use crate::{
+    assembler, config, cpp, depth, emul32,
+    error::Error,
+    launcher, linker,
+    personality::Personality,
+    posix, resolve, sysroot,
+    toolchain::{self, Toolchain},
+    trace::trace,
+};
+
+/// A resolved tool along with the arguments it will receive
+#[derive(Debug)]
+pub struct Invocation {
+    pub personality: Personality,
+    pub toolchain: Toolchain,
+    pub args: Vec<OsString>,
+}
+
+impl Invocation {
+    /// Resolve the tool for the personality and translate the caller's arguments
+    pub fn prepare(
+        personality: Personality,
+        args: impl IntoIterator<Item = OsString>,
+    ) -> Result<Self, Error> {
+        let mut toolchain =
+            resolve::toolchain(personality)?.ok_or(Error::NotFound(personality, None))?;
+        let tool = toolchain.tool();
+        let command = tool
+            .launcher
+            .iter()
+            .chain([&tool.program])
+            .chain(&tool.args);
+        trace!(
+            "resolved {} ({}, {})",
+            command.map(String::as_str).collect::<Vec<_>>().join(" "),
+            toolchain.family(),
+            tool.origin
+        );
+
+        let args = args.into_iter();
+        let mut args = match (personality, &toolchain) {
+            (Personality::CPP, Toolchain::LLVM(_)) => cpp::clang_args(args),
+            // llvm-mc takes its own arguments, only the driver needs translating
+            (Personality::AS, Toolchain::LLVM(clang))
+                if !toolchain::is_named(clang.name(), "llvm-mc") =>
+            {
+                assembler::clang_args(args)
+            }
+            (Personality::Posix(standard), _) => posix::args(standard, args)?,
+            _ => args.collect(),
+        };
+
+        // Multilib-less GCC would only fail at link time, so route 32-bit builds now
+        let emul32 = match toolchain {
+            Toolchain::Other(_) => None,
+            _ if personality.is_compiler() => emul32::requested(personality, &args),
+            _ => None,
+        };
+        if let Some((abi, inject)) = emul32 {
+            toolchain = emul32::route(personality, toolchain, abi)?;
+            if inject {
+                toolchain.tool_mut().args.push(abi.flag().to_owned());
+            }
+        }
+
+        // Unknown compilers can't be assumed to understand `-fuse-ld`
+        if personality.is_compiler() && !matches!(toolchain, Toolchain::Other(_)) {
+            if let Some(linker) = resolve::requested_linker(personality) {
+                args = linker::driver_args(linker, args);
+            }
+        }
+
+        // Configured flags go ahead of the caller's, which can then override them
+        let sysroot = sysroot::args(personality, &toolchain)?;
+        let tool = toolchain.tool_mut();
+        tool.args.extend(sysroot);
+        tool.args.extend(config::flags(personality));
+
+        // Launchers only make sense for compilation
+        if !personality.is_compiler() {
+            tool.launcher.clear();
+        } else if tool.launcher.is_empty() {
+            tool.launcher = launcher::from_env().unwrap_or_default();
+        }
+
+        Ok(Self {
+            personality,
+            toolchain,
+            args,
+        })
+    }
+
+    /// Build the command, along with a description of where the program came from
+    pub fn command(&self, depth: u32) -> (Command, String) {
+        let tool = self.toolchain.tool();
+
+        let mut launcher = tool.launcher.iter().filter(|l| {
+            let disabled = launcher::is_disabled(l);
+            if disabled {
+                trace!("launcher {l} is disabled, skipping");
+            }
+            !disabled
+        });
+
+        let (mut cmd, origin) = if let Some(first) = launcher.next() {
+            // Launchers inspect their own argv[0], so leave it be
+            let mut cmd = process::Command::new(first);
+            cmd.args(launcher);
+            cmd.arg(&tool.program);
+            (
+                cmd,
+                format!("launcher for {} {}", tool.program, tool.origin),
+            )
+        } else {
+            let mut cmd = process::Command::new(&tool.program);
+            cmd.arg0(self.personality.arg0());
+            (cmd, tool.origin.to_string())
+        };
+        cmd.args(&tool.args);
+        cmd.args(&self.args);
+        cmd.env(depth::VAR, depth.to_string());
+
+        (cmd, origin)
+    }
+
+    /// Reexecute process as the personality from whence we live, calling required toolchain
+    ///
+    /// Only returns if the exec failed.
+    pub fn exec(self, depth: u32) -> Error {
+        let (mut cmd, origin) = self.command(depth);
+
+        trace!("exec {cmd:?}");
+        let source = cmd.exec();
+        Error::Exec {
+            program: cmd.get_program().to_string_lossy().into_owned(),
+            origin,
+            source,
+        }
+    }
+}
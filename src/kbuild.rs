@@ -0,0 +1,127 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Kernel style toolchain variables, `CROSS_COMPILE` and `LLVM`
+//!
+//! Kbuild, U-Boot and various firmware trees prefix `CROSS_COMPILE`
+//! (`aarch64-linux-gnu-`) to each GNU tool, or use the LLVM suite when `LLVM` is set.
+//! `LLVM=1` uses the bare names, `LLVM=-18` a version suffix (`clang-18`, `llvm-ar-18`)
+//! and `LLVM=/opt/llvm/bin/` a directory. As with Kbuild, any other value (even `0`)
+//! means the bare names, and `LLVM` wins over `CROSS_COMPILE`, which then only picks
+//! clang's target.
+
+use std::env;
+
+use crate::{
+    search,
+    toolchain::{Origin, Tool, Toolchain},
+    trace::trace,
+};
+
+/// How `LLVM` spells the tools
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Llvm {
+    /// Appended to each name (`-18`), or empty for the bare names
+    Suffix(String),
+
+    /// Directory holding the tools (`/opt/llvm/bin/`)
+    Prefix(String),
+}
+
+impl Llvm {
+    fn from_env() -> Option<Self> {
+        Self::parse(&env::var("LLVM").ok()?)
+    }
+
+    /// Read a value of `LLVM`, `None` when empty
+    fn parse(value: &str) -> Option<Self> {
+        if value.is_empty() {
+            None
+        } else if value.ends_with('/') {
+            Some(Llvm::Prefix(value.to_owned()))
+        } else if value.starts_with('-') {
+            Some(Llvm::Suffix(value.to_owned()))
+        } else {
+            Some(Llvm::Suffix(String::new()))
+        }
+    }
+
+    fn name(&self, tool: &str) -> String {
+        match self {
+            Llvm::Suffix(suffix) => format!("{tool}{suffix}"),
+            Llvm::Prefix(prefix) => format!("{prefix}{tool}"),
+        }
+    }
+}
+
+/// Find a tool named by path or in `PATH`, never ourselves
+fn find(name: &str) -> Option<String> {
+    if !name.contains('/') {
+        return search::find_in_path(name);
+    }
+
+    if search::is_executable(name) && !search::is_self(name) {
+        trace!("  {name} found");
+        Some(name.to_owned())
+    } else {
+        trace!("  {name} not found");
+        None
+    }
+}
+
+/// The tool Kbuild would run, given its LLVM and GNU names (`llvm-ar`, `ar`)
+///
+/// `None` when neither variable is set or the tool isn't installed, leaving it to the
+/// usual resolution.
+pub fn tool(llvm_name: &str, gnu_name: &str) -> Option<Toolchain> {
+    llvm(llvm_name).or_else(|| gnu(gnu_name))
+}
+
+/// The LLVM tool Kbuild would run, if `LLVM` is set
+pub fn llvm(llvm_name: &str) -> Option<Toolchain> {
+    let llvm = Llvm::from_env()?;
+    trace!("LLVM is set, looking for {}", llvm.name(llvm_name));
+    let program = find(&llvm.name(llvm_name))?;
+
+    Some(Toolchain::LLVM(
+        Tool::new(program).with_origin(Origin::Env("LLVM".into())),
+    ))
+}
+
+/// The GNU tool Kbuild would run, or `None` under `LLVM` where clang stands in for it
+pub fn gnu(gnu_name: &str) -> Option<Toolchain> {
+    if Llvm::from_env().is_some() {
+        return None;
+    }
+
+    let prefix = env::var("CROSS_COMPILE").ok().filter(|p| !p.is_empty())?;
+    trace!("CROSS_COMPILE is set, looking for {prefix}{gnu_name}");
+    let program = find(&format!("{prefix}{gnu_name}"))?;
+
+    Some(Toolchain::GNU(
+        Tool::new(program).with_origin(Origin::Env("CROSS_COMPILE".into())),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Llvm;
+
+    fn name(value: &str) -> Option<String> {
+        Some(Llvm::parse(value)?.name("llvm-ar"))
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(name("1").as_deref(), Some("llvm-ar"));
+        // As with Kbuild, anything else is the bare names too
+        assert_eq!(name("0").as_deref(), Some("llvm-ar"));
+        assert_eq!(name("-18").as_deref(), Some("llvm-ar-18"));
+        assert_eq!(
+            name("/opt/llvm/bin/").as_deref(),
+            Some("/opt/llvm/bin/llvm-ar")
+        );
+        assert_eq!(name(""), None);
+    }
+}
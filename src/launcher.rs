@@ -0,0 +1,74 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Compiler launchers (`ccache`, `sccache`, `distcc`, `icecc`)
+//!
+//! Launchers prefix the real compiler (`CC="ccache clang"`), so they must be peeled
+//! off before classifying the compiler and put back in front of it at exec time.
+
+use std::{env, path::Path};
+
+use crate::{config, shell};
+
+/// Launchers we recognise in front of a compiler
+const KNOWN: &[&str] = &["ccache", "sccache", "distcc", "icecc", "icerun"];
+
+/// Whether the program is a known launcher
+pub fn is_launcher(program: &str) -> bool {
+    Path::new(program)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| KNOWN.contains(&n))
+}
+
+/// Launcher requested through `AUTOCC_LAUNCHER` or the `launcher` setting
+pub fn from_env() -> Option<Vec<String>> {
+    let var = config::var("AUTOCC_LAUNCHER")?;
+    let words = shell::split(&var)?;
+
+    (!words.is_empty()).then_some(words)
+}
+
+/// Whether the launcher has been switched off, i.e. `CCACHE_DISABLE`
+pub fn is_disabled(program: &str) -> bool {
+    let name = Path::new(program)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(program);
+
+    match name {
+        "ccache" => env::var("CCACHE_DISABLE").is_ok_and(|v| is_true(&v)),
+        _ => false,
+    }
+}
+
+/// How ccache reads a boolean variable: any value other than a negative one is true
+fn is_true(value: &str) -> bool {
+    !matches!(
+        value.to_ascii_lowercase().as_str(),
+        "0" | "false" | "disable" | "no"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_launcher, is_true};
+
+    #[test]
+    fn ccache_disable_values() {
+        for value in ["1", "true", "yes", "YES", "", "anything"] {
+            assert!(is_true(value), "{value:?} should disable ccache");
+        }
+        for value in ["0", "false", "FALSE", "disable", "Disable", "no"] {
+            assert!(!is_true(value), "{value:?} should leave ccache on");
+        }
+    }
+
+    #[test]
+    fn launchers() {
+        assert!(is_launcher("ccache"));
+        assert!(is_launcher("/usr/bin/sccache"));
+        assert!(!is_launcher("clang"));
+    }
+}
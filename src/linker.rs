@@ -0,0 +1,117 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Linker selection for the `ld` personality and the compiler drivers
+
+use std::{ffi::OsString, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+/// Linkers we know how to select
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Linker {
+    // GNU ld
+    BFD,
+
+    // GNU gold
+    Gold,
+
+    // LLVM lld
+    LLD,
+
+    // mold
+    Mold,
+}
+
+impl Linker {
+    /// Match a linker binary (`/usr/bin/ld.lld`) or `-fuse-ld` name (`lld`)
+    pub fn from_name(name: impl AsRef<Path>) -> Option<Self> {
+        match name.as_ref().file_name()?.to_str()? {
+            "bfd" | "ld.bfd" => Some(Linker::BFD),
+            "gold" | "ld.gold" => Some(Linker::Gold),
+            "lld" | "ld.lld" => Some(Linker::LLD),
+            "mold" | "ld.mold" => Some(Linker::Mold),
+            _ => None,
+        }
+    }
+
+    /// Name of the linker binary
+    pub fn binary(&self) -> &'static str {
+        match self {
+            Linker::BFD => "ld.bfd",
+            Linker::Gold => "ld.gold",
+            Linker::LLD => "ld.lld",
+            Linker::Mold => "mold",
+        }
+    }
+
+    /// Value for the compiler driver's `-fuse-ld=`
+    fn fuse_ld(&self) -> &'static str {
+        match self {
+            Linker::BFD => "bfd",
+            Linker::Gold => "gold",
+            Linker::LLD => "lld",
+            Linker::Mold => "mold",
+        }
+    }
+}
+
+/// Arguments that stop the driver before linking, making `-fuse-ld` unused
+const NON_LINKING: &[&str] = &["-c", "-S", "-E", "-M", "-MM", "-fsyntax-only"];
+
+/// Prepend `-fuse-ld=` for the linker, unless the caller chose one or isn't linking
+pub fn driver_args(linker: Linker, args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let args = args.into_iter().collect::<Vec<_>>();
+
+    let skip = args.iter().filter_map(|a| a.to_str()).any(|a| {
+        NON_LINKING.contains(&a) || a.starts_with("-fuse-ld=") || a.starts_with("--ld-path=")
+    });
+    if skip {
+        return args;
+    }
+
+    let mut flag = OsString::from("-fuse-ld=");
+    flag.push(linker.fuse_ld());
+
+    std::iter::once(flag).chain(args).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::{driver_args, Linker};
+
+    fn translate(args: &[&str]) -> Vec<OsString> {
+        driver_args(Linker::Mold, args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn links() {
+        assert_eq!(
+            translate(&["x.o", "-o", "x"]),
+            ["-fuse-ld=mold", "x.o", "-o", "x"]
+        );
+    }
+
+    #[test]
+    fn skips_when_not_linking() {
+        assert_eq!(translate(&["-c", "x.c"]), ["-c", "x.c"]);
+        assert_eq!(translate(&["-E", "x.c"]), ["-E", "x.c"]);
+    }
+
+    #[test]
+    fn caller_choice_wins() {
+        assert_eq!(translate(&["-fuse-ld=lld", "x.o"]), ["-fuse-ld=lld", "x.o"]);
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(Linker::from_name("/usr/bin/ld.lld"), Some(Linker::LLD));
+        assert_eq!(Linker::from_name("bfd"), Some(Linker::BFD));
+        assert_eq!(Linker::from_name("ld"), None);
+    }
+}
@@ -0,0 +1,257 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Determine which tool we're pretending to be from `argv[0]`
+
+use std::{ffi::OsStr, path::Path};
+
+use crate::{binutils::Binutil, cross, posix::Standard};
+
+/// The entrypoint autocc was invoked as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Personality {
+    /// C compiler driver (`cc`)
+    CC,
+
+    /// C++ compiler driver (`c++`, `CC`)
+    CXX,
+
+    /// C preprocessor (`cpp`, `/lib/cpp`), driven by the C toolchain
+    CPP,
+
+    /// POSIX C compiler utility pinned to a standard (`c89`, `c99`, `c17`)
+    Posix(Standard),
+
+    /// Binary utility matched to the C toolchain (`ar`, `nm`, ...)
+    Binutil(Binutil),
+
+    /// Linker (`ld`)
+    LD,
+
+    /// Assembler (`as`)
+    AS,
+
+    /// Native compiler for tools run during a cross build (`build-cc`, `build-c++`)
+    Build(Language),
+}
+
+impl Personality {
+    /// Work out the personality from the name we were invoked with
+    pub fn from_arg0(arg0: impl AsRef<OsStr>) -> Self {
+        let name = Path::new(arg0.as_ref())
+            .file_name()
+            .and_then(OsStr::to_str)
+            .unwrap_or_default();
+        // Cross tools are named for their target (`aarch64-linux-gnu-cc`)
+        let name = cross::split_name(name).map_or(name, |(_, tool)| tool);
+
+        Self::from_name(name).unwrap_or(Personality::CC)
+    }
+
+    /// Match a tool name (`c++`) to the personality
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cc" => Some(Personality::CC),
+            "c++" | "CC" => Some(Personality::CXX),
+            "cpp" => Some(Personality::CPP),
+            "ld" => Some(Personality::LD),
+            "as" => Some(Personality::AS),
+            "build-cc" => Some(Personality::Build(Language::C)),
+            "build-c++" => Some(Personality::Build(Language::CXX)),
+            x => Standard::from_name(x)
+                .map(Personality::Posix)
+                .or_else(|| Binutil::from_name(x).map(Personality::Binutil)),
+        }
+    }
+
+    /// Every personality, for reporting
+    pub fn all() -> impl Iterator<Item = Self> {
+        [
+            Personality::CC,
+            Personality::CXX,
+            Personality::CPP,
+            Personality::Posix(Standard::C89),
+            Personality::Posix(Standard::C99),
+            Personality::Posix(Standard::C17),
+            Personality::Binutil(Binutil::Ar),
+            Personality::Binutil(Binutil::Nm),
+            Personality::Binutil(Binutil::Ranlib),
+            Personality::Binutil(Binutil::Strip),
+            Personality::Binutil(Binutil::Objcopy),
+            Personality::LD,
+            Personality::AS,
+            Personality::Build(Language::C),
+            Personality::Build(Language::CXX),
+        ]
+        .into_iter()
+    }
+
+    /// Canonical name of the tool
+    pub fn name(&self) -> &'static str {
+        match self {
+            Personality::CC => "cc",
+            Personality::CXX => "c++",
+            Personality::CPP => "cpp",
+            Personality::Posix(standard) => standard.name(),
+            Personality::Binutil(tool) => tool.name(),
+            Personality::LD => "ld",
+            Personality::AS => "as",
+            Personality::Build(Language::C) => "build-cc",
+            Personality::Build(Language::CXX) => "build-c++",
+        }
+    }
+
+    /// Environment variable naming the user's tool of choice
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            Personality::CC | Personality::Posix(_) => "CC",
+            Personality::CXX => "CXX",
+            Personality::CPP => "CPP",
+            Personality::Binutil(tool) => tool.env_var(),
+            Personality::LD => "LD",
+            Personality::AS => "AS",
+            Personality::Build(language) => language.build_vars()[1],
+        }
+    }
+
+    /// Whether we're acting as a compiler driver
+    pub fn is_compiler(&self) -> bool {
+        matches!(
+            self,
+            Personality::CC | Personality::CXX | Personality::Posix(_) | Personality::Build(_)
+        )
+    }
+
+    /// Language of the compiler driver backing this personality
+    pub fn language(&self) -> Language {
+        match self {
+            Personality::CXX => Language::CXX,
+            Personality::Build(language) => *language,
+            _ => Language::C,
+        }
+    }
+
+    /// The `argv[0]` handed to the real tool
+    pub fn arg0(&self) -> String {
+        if let Personality::Build(language) = self {
+            return format!("/usr/bin/{}", language.driver_name());
+        }
+
+        match cross::target() {
+            Some(target) => format!("/usr/bin/{}", target.prefixed(self.name())),
+            None => format!("/usr/bin/{}", self.name()),
+        }
+    }
+}
+
+/// Language of a compiler driver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Language {
+    C,
+    CXX,
+}
+
+impl Language {
+    /// The compiler personality for the language
+    pub fn personality(&self) -> Personality {
+        match self {
+            Language::C => Personality::CC,
+            Language::CXX => Personality::CXX,
+        }
+    }
+
+    /// Environment variable naming the user's compiler of choice
+    pub fn compiler_var(&self) -> &'static str {
+        match self {
+            Language::C => "CC",
+            Language::CXX => "CXX",
+        }
+    }
+
+    /// Variables naming the build machine's compiler, by Kbuild, autotools and
+    /// others respectively
+    pub fn build_vars(&self) -> [&'static str; 3] {
+        match self {
+            Language::C => ["HOSTCC", "CC_FOR_BUILD", "BUILD_CC"],
+            Language::CXX => ["HOSTCXX", "CXX_FOR_BUILD", "BUILD_CXX"],
+        }
+    }
+
+    /// Generic name of the compiler driver
+    pub fn driver_name(&self) -> &'static str {
+        match self {
+            Language::C => "cc",
+            Language::CXX => "c++",
+        }
+    }
+
+    /// Name of the LLVM driver binary
+    pub fn llvm_driver(&self) -> &'static str {
+        match self {
+            Language::C => "clang",
+            Language::CXX => "clang++",
+        }
+    }
+
+    /// Name of the GNU driver binary
+    pub fn gnu_driver(&self) -> &'static str {
+        match self {
+            Language::C => "gcc",
+            Language::CXX => "g++",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Language, Personality};
+    use crate::{binutils::Binutil, posix::Standard};
+
+    #[test]
+    fn arg0() {
+        assert_eq!(Personality::from_arg0("/usr/bin/cc"), Personality::CC);
+        assert_eq!(Personality::from_arg0("/usr/bin/c++"), Personality::CXX);
+        assert_eq!(Personality::from_arg0("CC"), Personality::CXX);
+        assert_eq!(Personality::from_arg0("/lib/cpp"), Personality::CPP);
+        assert_eq!(
+            Personality::from_arg0("c99"),
+            Personality::Posix(Standard::C99)
+        );
+        assert_eq!(
+            Personality::from_arg0("build-c++"),
+            Personality::Build(Language::CXX)
+        );
+    }
+
+    #[test]
+    fn cross_names() {
+        assert_eq!(
+            Personality::from_arg0("/usr/bin/aarch64-linux-gnu-c++"),
+            Personality::CXX
+        );
+        assert_eq!(
+            Personality::from_arg0("riscv64-serpent-linux-ar"),
+            Personality::Binutil(Binutil::Ar)
+        );
+    }
+
+    #[test]
+    fn unknown_names_are_cc() {
+        assert_eq!(Personality::from_arg0("autocc-wrapper"), Personality::CC);
+        assert_eq!(Personality::from_arg0(""), Personality::CC);
+        assert_eq!(Personality::from_name("gcc"), None);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for personality in Personality::all() {
+            assert_eq!(
+                Personality::from_name(personality.name()),
+                Some(personality)
+            );
+        }
+    }
+}
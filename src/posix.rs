@@ -0,0 +1,173 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! POSIX `c89`, `c99` and `c17` utilities
+//!
+//! These behave like `cc` pinned to a single ISO C standard, refusing any attempt
+//! to select a different one.
+
+use std::{ffi::OsString, fmt};
+
+/// ISO C standard pinned by the invocation name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Standard {
+    C89,
+    C99,
+    C17,
+}
+
+impl Standard {
+    /// Match a utility name (`c99`) to the standard
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "c89" => Some(Standard::C89),
+            "c99" => Some(Standard::C99),
+            "c17" => Some(Standard::C17),
+            _ => None,
+        }
+    }
+
+    /// Name of the utility
+    pub fn name(&self) -> &'static str {
+        match self {
+            Standard::C89 => "c89",
+            Standard::C99 => "c99",
+            Standard::C17 => "c17",
+        }
+    }
+
+    /// The `-std=` flag we inject
+    fn flag(&self) -> &'static str {
+        match self {
+            Standard::C89 => "-std=c89",
+            Standard::C99 => "-std=c99",
+            Standard::C17 => "-std=c17",
+        }
+    }
+
+    /// All spellings selecting this exact standard
+    fn spellings(&self) -> &'static [&'static str] {
+        match self {
+            Standard::C89 => &["-ansi", "-std=c89", "-std=c90", "-std=iso9899:1990"],
+            Standard::C99 => &[
+                "-std=c99",
+                "-std=c9x",
+                "-std=iso9899:1999",
+                "-std=iso9899:199x",
+            ],
+            Standard::C17 => &[
+                "-std=c17",
+                "-std=c18",
+                "-std=iso9899:2017",
+                "-std=iso9899:2018",
+            ],
+        }
+    }
+}
+
+/// The caller asked for a standard other than the one we're pinned to
+#[derive(Debug)]
+pub struct ConflictingStandard {
+    standard: Standard,
+    option: String,
+}
+
+impl fmt::Display for ConflictingStandard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} called with non ISO {} option {}",
+            self.standard.name(),
+            self.standard.name().to_uppercase(),
+            self.option
+        )
+    }
+}
+
+impl std::error::Error for ConflictingStandard {}
+
+/// Apply POSIX option semantics and pin the standard
+pub fn args(
+    standard: Standard,
+    args: impl IntoIterator<Item = OsString>,
+) -> Result<Vec<OsString>, ConflictingStandard> {
+    let mut result = vec![];
+    let mut pinned = false;
+
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some(x) if standard.spellings().contains(&x) => pinned = true,
+            Some(x) if x == "-ansi" || x.starts_with("-std=") => {
+                return Err(ConflictingStandard {
+                    standard,
+                    option: x.to_owned(),
+                })
+            }
+            // POSIX permits `-O optlevel` as two arguments
+            Some("-O") => {
+                let level = args.next_if(|l| {
+                    l.to_str()
+                        .is_some_and(|l| !l.is_empty() && l.bytes().all(|b| b.is_ascii_digit()))
+                });
+                if let Some(level) = level {
+                    let mut joined = OsString::from("-O");
+                    joined.push(level);
+                    result.push(joined);
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        result.push(arg);
+    }
+
+    if !pinned {
+        result.insert(0, standard.flag().into());
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::{args, Standard};
+
+    fn translate(standard: Standard, list: &[&str]) -> Option<Vec<OsString>> {
+        args(standard, list.iter().map(OsString::from)).ok()
+    }
+
+    #[test]
+    fn pins_standard() {
+        assert_eq!(
+            translate(Standard::C99, &["-c", "x.c"]).unwrap(),
+            ["-std=c99", "-c", "x.c"]
+        );
+        assert_eq!(
+            translate(Standard::C89, &["-ansi", "x.c"]).unwrap(),
+            ["-ansi", "x.c"]
+        );
+    }
+
+    #[test]
+    fn joins_optimisation_level() {
+        assert_eq!(
+            translate(Standard::C99, &["-O", "2", "x.c"]).unwrap(),
+            ["-std=c99", "-O2", "x.c"]
+        );
+        // Only a level is joined
+        assert_eq!(
+            translate(Standard::C99, &["-O", "x.c"]).unwrap(),
+            ["-std=c99", "-O", "x.c"]
+        );
+    }
+
+    #[test]
+    fn rejects_other_standards() {
+        assert!(translate(Standard::C99, &["-std=gnu99", "x.c"]).is_none());
+        assert!(translate(Standard::C17, &["-ansi"]).is_none());
+    }
+}
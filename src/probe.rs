@@ -0,0 +1,196 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Ask a compiler about itself
+//!
+//! Each probe spawns the compiler, so they're only used when reporting, when
+//! explicitly requested with `AUTOCC_PROBE=1`, or to check GCC's multilibs for `-m32`.
+
+use std::{
+    collections::HashMap,
+    fs,
+    process::{Command, Stdio},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    linker::Linker,
+    search,
+    toolchain::{Family, Tool},
+    trace::trace,
+    version::Version,
+};
+
+/// Whether compilers named by the environment should be probed rather than
+/// classified by name
+pub fn enabled() -> bool {
+    config::flag("AUTOCC_PROBE", false)
+}
+
+/// Run the compiler with the arguments, returning its output
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        trace!("  {program} {} failed: {}", args.join(" "), output.status);
+        return None;
+    }
+
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Run the compiler with the arguments, returning the first line of its output
+fn query(program: &str, args: &[&str]) -> Option<String> {
+    let line = output(program, args)?.lines().next()?.trim().to_owned();
+    (!line.is_empty()).then_some(line)
+}
+
+/// The compiler's version, via `-dumpfullversion` (GCC 7+) or `-dumpversion`
+pub fn version(program: &str) -> Option<Version> {
+    query(program, &["-dumpfullversion"])
+        .and_then(|v| v.parse().ok())
+        .or_else(|| query(program, &["-dumpversion"])?.parse().ok())
+}
+
+/// The compiler's default target triple, via `-dumpmachine`
+pub fn target(program: &str) -> Option<String> {
+    query(program, &["-dumpmachine"])
+}
+
+/// The multilib option sets the compiler ships libraries for (`-m32`, `-mx32`), via
+/// `-print-multi-lib`
+pub fn multilibs(tool: &Tool) -> Vec<String> {
+    output(&tool.program, &with_args(tool, &["-print-multi-lib"]))
+        .map(|output| multilib_options(&output))
+        .unwrap_or_default()
+}
+
+/// The option sets in `-print-multi-lib` output, one `dir;@opt@opt` line per
+/// multilib, the default being `.;`
+fn multilib_options(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(';'))
+        .map(|(_, options)| options.replace('@', " -").trim().to_owned())
+        .filter(|options| !options.is_empty())
+        .collect()
+}
+
+/// What a compiler says about itself
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Identity {
+    pub family: Family,
+    pub version: Option<Version>,
+    pub target: Option<String>,
+    /// First line of `--version`, naming vendor builds (`Apple clang`, `Ubuntu gcc`)
+    pub banner: Option<String>,
+    /// Linker the driver uses without `-fuse-ld`
+    pub linker: Option<Linker>,
+}
+
+/// Identify the compiler from its predefined macros, `None` if it can't preprocess
+pub fn identify(tool: &Tool) -> Option<Identity> {
+    trace!("  probing {}", tool.program);
+    let run = |args: &[&'static str]| with_args(tool, args);
+
+    let macros = output(&tool.program, &run(&["-dM", "-E", "-x", "c", "/dev/null"]))?
+        .lines()
+        .filter_map(|l| {
+            let mut words = l.strip_prefix("#define ")?.splitn(2, ' ');
+            Some((
+                words.next()?.to_owned(),
+                words.next().unwrap_or_default().to_owned(),
+            ))
+        })
+        .collect::<HashMap<_, _>>();
+    let release = |parts: [&str; 3]| {
+        let parts = parts.map(|p| macros.get(p).map(String::as_str));
+        let [Some(major), minor, patch] = parts else {
+            return None;
+        };
+        format!("{major}.{}.{}", minor.unwrap_or("0"), patch.unwrap_or("0"))
+            .parse()
+            .ok()
+    };
+
+    // clang defines __GNUC__ too, so check for it first
+    let (family, version) = if macros.contains_key("__clang__") {
+        let version = release(["__clang_major__", "__clang_minor__", "__clang_patchlevel__"]);
+        (Family::LLVM, version)
+    } else if macros.contains_key("__GNUC__") {
+        let version = release(["__GNUC__", "__GNUC_MINOR__", "__GNUC_PATCHLEVEL__"]);
+        (Family::GNU, version)
+    } else {
+        (Family::Other, None)
+    };
+
+    let identity = Identity {
+        family,
+        version: version.or_else(|| version_of(tool)),
+        target: query(&tool.program, &run(&["-dumpmachine"])),
+        banner: query(&tool.program, &run(&["--version"])),
+        linker: default_linker(tool, &run(&["-print-prog-name=ld"])),
+    };
+    trace!("  identified as {identity:?}");
+
+    Some(identity)
+}
+
+/// The tool's own arguments followed by the probe's
+fn with_args<'a>(tool: &'a Tool, args: &[&'a str]) -> Vec<&'a str> {
+    tool.args
+        .iter()
+        .map(String::as_str)
+        .chain(args.iter().copied())
+        .collect()
+}
+
+/// Version via the dump options, taking the tool's own arguments into account
+fn version_of(tool: &Tool) -> Option<Version> {
+    let args = tool.args.iter().map(String::as_str);
+    ["-dumpfullversion", "-dumpversion"]
+        .into_iter()
+        .find_map(|dump| {
+            let args = args.clone().chain([dump]).collect::<Vec<_>>();
+            query(&tool.program, &args)?.parse().ok()
+        })
+}
+
+/// The linker the driver runs, following `ld` to the implementation it links to
+fn default_linker(tool: &Tool, args: &[&str]) -> Option<Linker> {
+    let ld = query(&tool.program, args)?;
+    let path = if ld.contains('/') {
+        ld.clone()
+    } else {
+        search::find_in_path(&ld)?
+    };
+    let path = fs::canonicalize(&path).ok()?;
+    let name = path.file_name()?.to_str()?;
+
+    // Strip any triple (x86_64-linux-gnu-ld.bfd)
+    Linker::from_name(name.rsplit('-').next()?).or_else(|| Linker::from_name(&ld))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::multilib_options;
+
+    #[test]
+    fn multilibs() {
+        assert_eq!(
+            multilib_options(".;\n32;@m32\nx32;@mx32\n"),
+            ["-m32", "-mx32"]
+        );
+        assert_eq!(multilib_options(".;\n64/32;@m64@m32\n"), ["-m64 -m32"]);
+        assert!(multilib_options(".;\n").is_empty());
+        assert!(multilib_options("").is_empty());
+    }
+}
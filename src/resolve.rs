@@ -0,0 +1,421 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Resolve the real tool to run for each personality
+
+use crate::{
+    binutils::Binutil,
+    cache, config,
+    cross::{self, Target},
+    discovery::{self, Requirement},
+    error::Error,
+    kbuild,
+    linker::Linker,
+    personality::{Language, Personality},
+    search::{self, find_in_path, tool_relative_to_path},
+    toolchain::{self, Family, Origin, Tool, Toolchain},
+    trace::trace,
+};
+
+/// A resolved tool, `None` when there's nothing suitable, or an error when what was
+/// explicitly asked for can't be found
+pub type Resolved = Result<Option<Toolchain>, Error>;
+
+/// Resolve the tool backing the personality
+pub fn toolchain(personality: Personality) -> Resolved {
+    cache::get_or_resolve(personality, |personality| match personality {
+        Personality::CPP => preprocessor(),
+        Personality::Binutil(tool) => binutil(tool),
+        Personality::LD => linker(),
+        Personality::AS => assembler(),
+        Personality::Build(language) => build_compiler(language),
+        _ => compiler(personality.language()),
+    })
+}
+
+/// Try to return the correct toolchain based on the environment
+fn toolchain_from_environment(language: Language) -> Resolved {
+    let gnu = language.gnu_driver();
+
+    // Query CC (or CXX) var, an explicit choice is always respected
+    if let Some(cc) = Tool::from_env(language.compiler_var()) {
+        return Ok(Some(Toolchain::identify(language, cc)));
+    }
+
+    // Kernel style variables, LLVM=1 or CROSS_COMPILE=aarch64-linux-gnu-
+    if let Some(toolchain) = kbuild::tool(language.llvm_driver(), language.gnu_driver()) {
+        return Ok(Some(toolchain));
+    }
+
+    // A toolchain chosen without touching CC, which build systems tend to record. As
+    // with CC, it's that or nothing
+    if let Some(choice) = config::var("AUTOCC_TOOLCHAIN") {
+        trace!("AUTOCC_TOOLCHAIN = {choice:?}");
+        let toolchain = if choice.contains('/') {
+            Some(toolchain_from_compiler(language, &choice)?)
+        } else if let Some(family) = Family::from_name(&choice) {
+            Some(
+                toolchain_from_families(language, &[family], cross::target())?.map(
+                    |mut toolchain| {
+                        let tool = toolchain.tool_mut();
+                        if tool.origin == Origin::Path {
+                            tool.origin = Origin::Env("AUTOCC_TOOLCHAIN".into());
+                        }
+                        toolchain
+                    },
+                ),
+            )
+        } else {
+            trace!("  not a family or a path, ignoring");
+            None
+        };
+        if let Some(toolchain) = toolchain {
+            let not_found = Error::NotFound(language.personality(), Some("AUTOCC_TOOLCHAIN"));
+            return toolchain.map(Some).ok_or(not_found);
+        }
+    }
+
+    // Query LD var
+    if let Some(ld) = Tool::from_env("LD") {
+        match ld.name() {
+            "lld" | "ld.lld" => {
+                trace!("  LD hints at LLVM, looking for {}", language.llvm_driver());
+                let clang = tool_relative_to_path(&ld.program, language.llvm_driver());
+                return Ok(clang.map(|c| Toolchain::LLVM(Tool::new(c).with_origin(ld.origin))));
+            }
+            x if x == "ld" || x.starts_with("ld.") => {
+                trace!("  LD hints at GNU, looking for {gnu}");
+                let gcc = tool_relative_to_path(&ld.program, gnu);
+                return Ok(gcc.map(|g| Toolchain::GNU(Tool::new(g).with_origin(ld.origin))));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(None)
+}
+
+/// Resolve the compiler named by `AUTOCC_TOOLCHAIN`, taken to be the C compiler
+///
+/// For C++ its sibling driver is used (`g++-14` next to `gcc-14`), or failing that
+/// the same family found elsewhere.
+fn toolchain_from_compiler(language: Language, path: &str) -> Resolved {
+    let origin = Origin::Env("AUTOCC_TOOLCHAIN".into());
+    let Some(program) = search::resolve_program(path) else {
+        return Ok(None);
+    };
+    let toolchain = Toolchain::identify(Language::C, Tool::new(program).with_origin(origin));
+    if language == Language::C {
+        return Ok(Some(toolchain));
+    }
+
+    let family = toolchain.kind();
+    let name = toolchain.tool().name();
+    let sibling = match family {
+        Family::GNU => name.replacen(Language::C.gnu_driver(), language.gnu_driver(), 1),
+        Family::LLVM => name.replacen(Language::C.llvm_driver(), language.llvm_driver(), 1),
+        Family::Other => return Ok(None),
+    };
+    match tool_relative_to_path(&toolchain.tool().program, sibling) {
+        Some(program) => Ok(Some(family.toolchain(Tool {
+            program,
+            ..toolchain.tool().clone()
+        }))),
+        // Settle for the same family elsewhere
+        None => toolchain_from_families(language, &[family], cross::target()),
+    }
+}
+
+/// Families to search for, in order, from `AUTOCC_PREFER` or LLVM then GNU
+pub fn preference() -> Vec<Family> {
+    let default = vec![Family::LLVM, Family::GNU];
+    let Some(prefer) = config::var("AUTOCC_PREFER") else {
+        return default;
+    };
+
+    let mut families = vec![];
+    for name in prefer.split(',').filter(|n| !n.trim().is_empty()) {
+        match Family::from_name(name) {
+            Some(family) if !families.contains(&family) => families.push(family),
+            Some(_) => {}
+            None => trace!("AUTOCC_PREFER names unknown family {name:?}, ignoring"),
+        }
+    }
+
+    if families.is_empty() {
+        default
+    } else {
+        trace!("AUTOCC_PREFER = {prefer:?}");
+        families
+    }
+}
+
+/// Search for the first of the families installed
+///
+/// When cross compiling for `target`, a compiler built for it (`aarch64-linux-gnu-gcc`)
+/// is preferred as it brings its own headers and libraries, then clang, which
+/// [`compiler`] retargets. A family with a pinned release is never passed over for
+/// the next, as the pin asks for that family.
+fn toolchain_from_families(
+    language: Language,
+    families: &[Family],
+    target: Option<&Target>,
+) -> Resolved {
+    let find = |family: Family, driver: &str| {
+        let tool = discovery::select(driver, family.version_var()?)?;
+        Some(family.toolchain(tool))
+    };
+    let pinned = |family: Family| {
+        family
+            .version_var()
+            .filter(|var| Requirement::from_env(var).is_some())
+    };
+    // A native triple (`x86_64-serpent-linux-cc`) keeps the usual preference
+    let target = target.filter(|t| !t.is_native());
+
+    for &family in families {
+        let Some(driver) = family.driver(language) else {
+            continue;
+        };
+        let found = match target {
+            Some(target) => find(family, &target.prefixed(driver)),
+            None => find(family, driver),
+        };
+        if found.is_some() {
+            return Ok(found);
+        }
+
+        if let Some(var) = pinned(family) {
+            // A pinned clang may still be retargeted
+            if target.is_some() && family == Family::LLVM {
+                if let Some(found) = find(family, driver) {
+                    return Ok(Some(found));
+                }
+            }
+            trace!("  nothing satisfies {var}");
+            return Err(Error::NotFound(language.personality(), Some(var)));
+        }
+    }
+
+    if target.is_some() && families.contains(&Family::LLVM) {
+        return Ok(find(Family::LLVM, language.llvm_driver()));
+    }
+
+    Ok(None)
+}
+
+/// Check well known filesystesm path
+pub fn toolchain_from_filesystem(language: Language) -> Resolved {
+    toolchain_from_families(language, &preference(), cross::target())
+}
+
+/// Resolve the compiler driver for the language
+pub fn compiler(language: Language) -> Resolved {
+    let toolchain = match toolchain_from_environment(language)? {
+        Some(toolchain) => Some(toolchain),
+        None => toolchain_from_filesystem(language)?,
+    };
+
+    Ok(toolchain.map(retarget))
+}
+
+/// Resolve the native compiler for tools run during the build
+///
+/// `CC`, `LD`, `CROSS_COMPILE` and `AUTOCC_TOOLCHAIN` describe the target, so only the
+/// build machine's own variables are consulted before searching the filesystem.
+/// `LLVM` still applies, as with Kbuild's `HOSTCC`.
+fn build_compiler(language: Language) -> Resolved {
+    for var in language.build_vars() {
+        if let Some(cc) = Tool::from_env(var) {
+            return Ok(Some(Toolchain::identify(language, cc)));
+        }
+    }
+
+    match kbuild::llvm(language.llvm_driver()) {
+        Some(clang) => Ok(Some(clang)),
+        // The host's own compiler, whatever the target
+        None => toolchain_from_families(language, &preference(), None),
+    }
+}
+
+/// Point clang at the cross target, unless it's been given one already
+fn retarget(mut toolchain: Toolchain) -> Toolchain {
+    let (Some(target), Toolchain::LLVM(clang)) = (cross::target(), &mut toolchain) else {
+        return toolchain;
+    };
+
+    if !clang
+        .args
+        .iter()
+        .any(|a| a == "-target" || a.starts_with("--target="))
+    {
+        trace!("  retargeting {} to {}", clang.program, target.triple);
+        clang.args.push(format!("--target={}", target.triple));
+    }
+
+    toolchain
+}
+
+/// A GNU tool's name for the cross target (`aarch64-linux-gnu-ar`), if any
+fn gnu_tool(tool: &str) -> String {
+    match cross::target() {
+        Some(target) => target.prefixed(tool),
+        None => tool.to_owned(),
+    }
+}
+
+/// Resolve the preprocessor, honouring `CPP` before falling back to the C toolchain
+fn preprocessor() -> Resolved {
+    // Anything but clang is taken to be GNU cpp
+    if let Some(cpp) = Tool::from_env("CPP") {
+        return Ok(Some(match Toolchain::classify(Language::C, cpp) {
+            Toolchain::Other(cpp) => Toolchain::GNU(cpp),
+            toolchain => toolchain,
+        }));
+    }
+
+    let Some(compiler) = compiler(Language::C)? else {
+        return Ok(None);
+    };
+    Ok(match compiler {
+        Toolchain::GNU(gcc) => companion(&gcc, &gnu_tool("cpp"))
+            .map(|cpp| Toolchain::GNU(Tool::new(cpp).with_origin(Origin::Compiler))),
+        Toolchain::LLVM(clang) => Some(Toolchain::LLVM(clang)),
+        Toolchain::Other(cc) => Some(Toolchain::Other(cc.with_args(["-E"]))),
+    })
+}
+
+/// Resolve a binary utility, honouring its variable before matching the C toolchain
+fn binutil(tool: Binutil) -> Resolved {
+    if let Some(var) = Tool::from_env(tool.env_var()) {
+        return Ok(Some(
+            if var.name().starts_with("llvm-") || toolchain::is_named(var.name(), tool.llvm_name())
+            {
+                Toolchain::LLVM(var)
+            } else {
+                Toolchain::GNU(var)
+            },
+        ));
+    }
+
+    if let Some(toolchain) = kbuild::tool(tool.llvm_name(), tool.name()) {
+        return Ok(Some(toolchain));
+    }
+
+    let Some(compiler) = compiler(Language::C)? else {
+        return Ok(None);
+    };
+    Ok(match compiler {
+        Toolchain::GNU(gcc) => companion(&gcc, &gnu_tool(tool.gnu_name()))
+            .map(|t| Toolchain::GNU(Tool::new(t).with_origin(Origin::Compiler))),
+        Toolchain::LLVM(clang) => companion(&clang, tool.llvm_name())
+            .map(|t| Toolchain::LLVM(Tool::new(t).with_origin(Origin::Compiler))),
+        Toolchain::Other(_) => find_in_path(tool.name()).map(|t| Toolchain::Other(Tool::new(t))),
+    })
+}
+
+/// Find a tool shipped with the compiler, preferring one with the same version
+/// suffix (`gcc-ar-13` for `gcc-13`), then its directory, then `PATH`
+fn companion(compiler: &Tool, tool: &str) -> Option<String> {
+    compiler
+        .version_suffix()
+        .and_then(|v| tool_relative_to_path(&compiler.program, format!("{tool}-{v}")))
+        .or_else(|| tool_relative_to_path(&compiler.program, tool))
+        .or_else(|| find_in_path(tool))
+}
+
+/// Resolve the assembler, honouring `AS` before matching the C toolchain
+fn assembler() -> Resolved {
+    if let Some(var) = Tool::from_env("AS") {
+        return Ok(Some(
+            if ["clang", "llvm-mc"]
+                .iter()
+                .any(|llvm| toolchain::is_named(var.name(), llvm))
+            {
+                Toolchain::LLVM(var)
+            } else {
+                Toolchain::GNU(var)
+            },
+        ));
+    }
+
+    // Under LLVM clang assembles, as below
+    if let Some(toolchain) = kbuild::gnu("as") {
+        return Ok(Some(toolchain));
+    }
+
+    let Some(compiler) = compiler(Language::C)? else {
+        return Ok(None);
+    };
+    Ok(match compiler {
+        Toolchain::GNU(gcc) => companion(&gcc, &gnu_tool("as"))
+            .map(|t| Toolchain::GNU(Tool::new(t).with_origin(Origin::Compiler))),
+        Toolchain::LLVM(clang) => Some(Toolchain::LLVM(clang)),
+        Toolchain::Other(_) => find_in_path("as").map(|t| Toolchain::Other(Tool::new(t))),
+    })
+}
+
+/// The linker requested through `LD`, or the `AUTOCC_LINKER` preference
+///
+/// `LD` is the target's linker, so doesn't apply to the build machine's compiler.
+pub fn requested_linker(personality: Personality) -> Option<Linker> {
+    let ld = match personality {
+        Personality::Build(_) => None,
+        _ => Tool::from_env("LD"),
+    };
+
+    ld.and_then(|ld| Linker::from_name(ld.name()))
+        .or_else(|| Linker::from_name(config::var("AUTOCC_LINKER")?))
+}
+
+/// Resolve the linker, honouring `LD` before the preference or the C toolchain default
+fn linker() -> Resolved {
+    if let Some(ld) = Tool::from_env("LD") {
+        return Ok(Some(if Linker::from_name(ld.name()) == Some(Linker::LLD) {
+            Toolchain::LLVM(ld)
+        } else {
+            Toolchain::GNU(ld)
+        }));
+    }
+
+    if let Some(toolchain) = kbuild::tool(Linker::LLD.binary(), "ld") {
+        return Ok(Some(toolchain));
+    }
+
+    let linker = match Linker::from_name(config::var("AUTOCC_LINKER").unwrap_or_default()) {
+        Some(linker) => linker,
+        None => {
+            let Some(compiler) = compiler(Language::C)? else {
+                return Ok(None);
+            };
+            // A probed driver knows which linker it would use
+            match (
+                compiler.tool().identity.as_ref().and_then(|i| i.linker),
+                &compiler,
+            ) {
+                (Some(linker), _) => linker,
+                (None, Toolchain::LLVM(_)) => Linker::LLD,
+                (None, _) => Linker::BFD,
+            }
+        }
+    };
+    trace!("selected linker {linker:?}");
+
+    // GNU linkers are built per target, the others handle them all
+    let path = match (cross::target(), linker) {
+        (Some(target), Linker::BFD) => find_in_path(target.prefixed(linker.binary()))
+            .or_else(|| find_in_path(target.prefixed("ld"))),
+        (Some(target), Linker::Gold) => find_in_path(target.prefixed(linker.binary())),
+        _ => find_in_path(linker.binary()),
+    };
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let path = Tool::new(path);
+    Ok(Some(if linker == Linker::LLD {
+        Toolchain::LLVM(path)
+    } else {
+        Toolchain::GNU(path)
+    }))
+}
@@ -0,0 +1,110 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Locate tools on the filesystem, never resolving to autocc itself
+//!
+//! autocc is installed under the same names as the tools it stands in for, so any
+//! lookup may find us first. Following such a candidate would exec in a loop.
+
+use std::{
+    env, fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use crate::trace::trace;
+
+/// Whether the path is (a link to) our own executable
+pub fn is_self(path: impl AsRef<Path>) -> bool {
+    static SELF: OnceLock<Option<(u64, u64)>> = OnceLock::new();
+
+    let identity = |path: &Path| fs::metadata(path).ok().map(|m| (m.dev(), m.ino()));
+    let Some(ours) = SELF.get_or_init(|| identity(Path::new("/proc/self/exe"))) else {
+        return false;
+    };
+
+    identity(path.as_ref()).as_ref() == Some(ours)
+}
+
+/// The search path, with a sane default when unset
+fn search_path() -> String {
+    env::var("PATH").unwrap_or_else(|_| "/usr/local/bin:/usr/bin:/bin".into())
+}
+
+/// Attempt to find the tool relative to the path given (same dir)
+pub fn tool_relative_to_path(path: impl AsRef<Path>, tool: impl AsRef<Path>) -> Option<String> {
+    let path = PathBuf::from(path.as_ref());
+    let input_path = path.parent().filter(|p| !p.as_os_str().is_empty())?;
+    let tool_path = input_path.join(tool);
+    if !tool_path.exists() {
+        trace!("  {} not found", tool_path.display());
+        None
+    } else if is_self(&tool_path) {
+        trace!("  {} is autocc, skipping", tool_path.display());
+        None
+    } else {
+        trace!("  {} found", tool_path.display());
+        Some(tool_path.to_str()?.to_owned())
+    }
+}
+
+/// Every match for the tool in `PATH`, in search order, skipping ourselves
+fn path_candidates(name: &Path) -> impl Iterator<Item = String> + '_ {
+    trace!("searching PATH for {}", name.display());
+    env::split_paths(&search_path())
+        .collect::<Vec<_>>()
+        .into_iter()
+        .filter_map(move |p| {
+            let tool_path = p.join(name);
+            if !tool_path.exists() {
+                None
+            } else if is_self(&tool_path) {
+                trace!("  {} is autocc, skipping", tool_path.display());
+                None
+            } else {
+                trace!("  {} found", tool_path.display());
+                Some(tool_path.to_string_lossy().to_string())
+            }
+        })
+}
+
+/// Find the first match for the tool in `PATH`, skipping ourselves
+pub fn find_in_path(name: impl AsRef<Path>) -> Option<String> {
+    path_candidates(name.as_ref()).next()
+}
+
+/// Whether the program (a path, or a name to search `PATH` for) can be executed
+pub fn is_executable(program: &str) -> bool {
+    if !program.contains('/') {
+        return find_in_path(program).is_some();
+    }
+
+    fs::metadata(program).is_ok_and(|m| m.is_file() && m.mode() & 0o111 != 0)
+}
+
+/// Resolve a program as `execvp` would, or `None` if that would only find ourselves
+///
+/// Programs that can't be found at all are returned unchanged, so that running
+/// them reports the problem.
+pub fn resolve_program(program: &str) -> Option<String> {
+    if program.contains('/') {
+        if is_self(program) {
+            trace!("  {program} is autocc, ignoring");
+            return None;
+        }
+        return Some(program.to_owned());
+    }
+
+    find_in_path(program).or_else(|| {
+        let shadowed = env::split_paths(&search_path()).any(|p| is_self(p.join(program)));
+        if shadowed {
+            trace!("  {program} only resolves to autocc, ignoring");
+            None
+        } else {
+            trace!("  {program} not found in PATH, leaving it to exec");
+            Some(program.to_owned())
+        }
+    })
+}
@@ -0,0 +1,89 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Split command strings (`CC="clang -m32"`) into words following POSIX shell
+//! quoting rules, without any expansion
+
+/// Split the input into words, or `None` if a quote or escape is left unterminated
+pub fn split(input: &str) -> Option<Vec<String>> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_ascii_whitespace() => words.extend(word.take()),
+            '\\' => match chars.next()? {
+                // Line continuation
+                '\n' => {}
+                c => word.get_or_insert_with(String::new).push(c),
+            },
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        // Backslash only escapes these within double quotes
+                        '\\' => match chars.next()? {
+                            '\n' => {}
+                            c @ ('$' | '`' | '"' | '\\') => word.push(c),
+                            c => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+
+    Some(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split;
+
+    #[test]
+    fn words() {
+        assert_eq!(split("  clang   -m32 ").unwrap(), ["clang", "-m32"]);
+        assert_eq!(split("").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn quotes() {
+        assert_eq!(
+            split(r#"gcc '-DNAME="a b"' "-DDIR=/opt/my dir""#).unwrap(),
+            ["gcc", r#"-DNAME="a b""#, "-DDIR=/opt/my dir"]
+        );
+        assert_eq!(split("''").unwrap(), [""]);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(split(r"a\ b c\\d").unwrap(), ["a b", r"c\d"]);
+        assert_eq!(split(r#""\$x \"q\" \n""#).unwrap(), [r#"$x "q" \n"#]);
+        assert_eq!(split("a\\\nb").unwrap(), ["ab"]);
+    }
+
+    #[test]
+    fn unterminated() {
+        assert_eq!(split("'open"), None);
+        assert_eq!(split("\"open"), None);
+        assert_eq!(split("trailing\\"), None);
+    }
+}
@@ -0,0 +1,61 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! The sysroot holding the target's headers and libraries
+//!
+//! Builds inside a root, or for another target, find their headers and libraries under
+//! a sysroot rather than `/`. It's named by `AUTOCC_SYSROOT`, a `sysroot` setting in the
+//! target's table (`[target.aarch64-linux-gnu]`), or else the `sysroot` setting, and
+//! handed to the compiler, preprocessor and linker alike.
+
+use std::{env, path::Path};
+
+use crate::{
+    config::{self, Source},
+    cross,
+    error::Error,
+    personality::Personality,
+    toolchain::Toolchain,
+    trace::trace,
+};
+
+const VAR: &str = "AUTOCC_SYSROOT";
+
+/// The configured sysroot and where it came from, `None` if unset or empty
+fn get() -> Option<(String, Source)> {
+    let sysroot = match env::var(VAR) {
+        Ok(path) => Some((path, Source::Env(VAR))),
+        Err(_) => cross::target()
+            .and_then(|target| config::target(&target.triple, "sysroot"))
+            .or_else(|| config::setting(VAR)),
+    };
+
+    sysroot.filter(|(path, _)| !path.is_empty())
+}
+
+/// The `--sysroot` flag for the tool, if one is configured and it searches the
+/// target's headers or libraries
+pub fn args(personality: Personality, toolchain: &Toolchain) -> Result<Vec<String>, Error> {
+    let applies = match personality {
+        // The build machine's compiler uses its own headers and libraries
+        Personality::Build(_) => false,
+        Personality::LD => true,
+        // Unknown compilers can't be assumed to understand `--sysroot`
+        Personality::CPP => !matches!(toolchain, Toolchain::Other(_)),
+        _ => personality.is_compiler() && !matches!(toolchain, Toolchain::Other(_)),
+    };
+    if !applies {
+        return Ok(vec![]);
+    }
+
+    let Some((path, origin)) = get() else {
+        return Ok(vec![]);
+    };
+    if !Path::new(&path).is_dir() {
+        return Err(Error::Sysroot { path, origin });
+    }
+
+    trace!("using sysroot {path} ({origin})");
+    Ok(vec![format!("--sysroot={path}")])
+}
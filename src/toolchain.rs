@@ -0,0 +1,271 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Tools and the toolchain families they belong to
+
+use std::{borrow::Cow, env, fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    launcher,
+    personality::Language,
+    probe::{self, Identity},
+    search, shell,
+    trace::trace,
+    version::Version,
+};
+
+/// Where a tool came from, so failures can point at the cause
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Origin {
+    /// Named (or hinted at) by an environment variable
+    Env(Cow<'static, str>),
+
+    /// Discovered by searching `PATH`
+    Path,
+
+    /// Picked to match the resolved C compiler
+    Compiler,
+
+    /// The newest release satisfying a version requirement
+    Version(Cow<'static, str>),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Env(var) => write!(f, "from {var}"),
+            Origin::Path => write!(f, "from PATH"),
+            Origin::Compiler => write!(f, "matched to the C compiler"),
+            Origin::Version(var) => write!(f, "satisfying {var}"),
+        }
+    }
+}
+
+/// A program along with any arguments it must always be given
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tool {
+    /// Launchers the program is run through (`ccache`)
+    pub launcher: Vec<String>,
+    pub program: String,
+    pub args: Vec<String>,
+    pub origin: Origin,
+    /// What the program reported when probed
+    pub identity: Option<Identity>,
+}
+
+impl Tool {
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            launcher: vec![],
+            program: program.into(),
+            args: vec![],
+            origin: Origin::Path,
+            identity: None,
+        }
+    }
+
+    /// Record where the tool came from
+    pub fn with_origin(mut self, origin: Origin) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Append arguments always passed to the tool
+    pub fn with_args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Parse a command (`ccache clang -m32`) from the environment variable, if set
+    ///
+    /// A program that would only resolve back to autocc (`CC=cc`) is ignored.
+    pub fn from_env(name: &'static str) -> Option<Self> {
+        let Ok(var) = env::var(name) else {
+            trace!("{name} is unset");
+            return None;
+        };
+        trace!("{name} = {var:?}");
+        let Some(words) = shell::split(&var) else {
+            trace!("  {name} has unterminated quoting, ignoring");
+            return None;
+        };
+        let mut words = words.into_iter().peekable();
+
+        let mut launcher = vec![];
+        while let Some(word) = words.next_if(|w| launcher::is_launcher(w)) {
+            launcher.push(word);
+        }
+        let program = search::resolve_program(&words.next()?)?;
+
+        Some(Self {
+            launcher,
+            origin: Origin::Env(name.into()),
+            ..Self::new(program).with_args(words)
+        })
+    }
+
+    /// Binary *name* of the program
+    pub fn name(&self) -> &str {
+        Path::new(&self.program)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(&self.program)
+    }
+
+    /// Release carried in the binary name (`18` for `clang-18`)
+    pub fn version_suffix(&self) -> Option<Version> {
+        self.name().rsplit_once('-')?.1.parse().ok()
+    }
+}
+
+/// Family of a toolchain, without the tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Family {
+    GNU,
+    LLVM,
+    Other,
+}
+
+impl Family {
+    /// Parse a family name, accepting the driver names too
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim() {
+            "gnu" | "gcc" => Some(Family::GNU),
+            "llvm" | "clang" => Some(Family::LLVM),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Family::GNU => "gnu",
+            Family::LLVM => "llvm",
+            Family::Other => "other",
+        }
+    }
+
+    /// Driver binary for the language, if we know how to discover one
+    pub fn driver(&self, language: Language) -> Option<&'static str> {
+        match self {
+            Family::GNU => Some(language.gnu_driver()),
+            Family::LLVM => Some(language.llvm_driver()),
+            Family::Other => None,
+        }
+    }
+
+    /// Variable pinning the family's release
+    pub fn version_var(&self) -> Option<&'static str> {
+        match self {
+            Family::GNU => Some("AUTOCC_GCC_VERSION"),
+            Family::LLVM => Some("AUTOCC_CLANG_VERSION"),
+            Family::Other => None,
+        }
+    }
+
+    /// Wrap the tool as a toolchain of this family
+    pub fn toolchain(self, tool: Tool) -> Toolchain {
+        match self {
+            Family::GNU => Toolchain::GNU(tool),
+            Family::LLVM => Toolchain::LLVM(tool),
+            Family::Other => Toolchain::Other(tool),
+        }
+    }
+}
+
+/// We discover GNU (gcc) and LLVM (clang), but will run anything we're asked to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Toolchain {
+    // GNU (GCC)
+    GNU(Tool),
+
+    // LLVM (clang)
+    LLVM(Tool),
+
+    // Explicitly requested compiler of an unknown family (tcc, cproc, ...)
+    Other(Tool),
+}
+
+/// Whether the binary name is the tool, accepting versioned (`clang-18`) and
+/// triple-prefixed (`aarch64-linux-gnu-gcc`) spellings
+pub fn is_named(name: &str, tool: &str) -> bool {
+    name == tool
+        || name.starts_with(&format!("{tool}-"))
+        || name.ends_with(&format!("-{tool}"))
+        || name.contains(&format!("-{tool}-"))
+}
+
+impl Toolchain {
+    /// Classify a compiler by binary name, accepting versioned (`clang-18`) and
+    /// triple-prefixed (`aarch64-linux-gnu-gcc`) spellings
+    pub fn classify(language: Language, compiler: Tool) -> Self {
+        let name = compiler.name();
+        let is = |driver: &str| is_named(name, driver);
+
+        // Intel's oneAPI compilers are clang based
+        let toolchain = if is(language.llvm_driver()) || matches!(name, "icx" | "icpx") {
+            Toolchain::LLVM(compiler)
+        } else if is(language.gnu_driver()) {
+            Toolchain::GNU(compiler)
+        } else {
+            Toolchain::Other(compiler)
+        };
+        trace!("  classified as {}", toolchain.family());
+
+        toolchain
+    }
+
+    /// Identify a compiler by probing it when `AUTOCC_PROBE` is enabled, so `cc`
+    /// symlinks and renamed compilers are recognised, otherwise by name
+    pub fn identify(language: Language, mut compiler: Tool) -> Self {
+        if !probe::enabled() {
+            return Self::classify(language, compiler);
+        }
+
+        match probe::identify(&compiler) {
+            Some(identity) => {
+                let family = identity.family;
+                compiler.identity = Some(identity);
+                family.toolchain(compiler)
+            }
+            None => {
+                trace!("  probe failed, falling back to the name");
+                Self::classify(language, compiler)
+            }
+        }
+    }
+
+    pub fn kind(&self) -> Family {
+        match self {
+            Toolchain::GNU(_) => Family::GNU,
+            Toolchain::LLVM(_) => Family::LLVM,
+            Toolchain::Other(_) => Family::Other,
+        }
+    }
+
+    /// Short name of the family, for reporting
+    pub fn family(&self) -> &'static str {
+        self.kind().name()
+    }
+
+    pub fn tool(&self) -> &Tool {
+        match self {
+            Toolchain::GNU(t) => t,
+            Toolchain::LLVM(t) => t,
+            Toolchain::Other(t) => t,
+        }
+    }
+
+    pub fn tool_mut(&mut self) -> &mut Tool {
+        match self {
+            Toolchain::GNU(t) => t,
+            Toolchain::LLVM(t) => t,
+            Toolchain::Other(t) => t,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Resolution trace, enabled with `AUTOCC_DEBUG` or `AUTOCC_TRACE`
+//!
+//! `AUTOCC_DEBUG=1` traces to stderr. `AUTOCC_TRACE` takes a file to append to, so
+//! the trace doesn't interleave with the compiler's own diagnostics, or `stderr`. The
+//! `debug` and `trace` settings do the same from the configuration files.
+
+use std::{
+    fmt::Arguments,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    process,
+    sync::{Mutex, OnceLock},
+};
+
+use crate::config;
+
+/// Where trace lines end up
+enum Sink {
+    Stderr,
+    File(File),
+    /// Kept for `autocc explain`
+    Memory(Vec<String>),
+}
+
+static SINK: OnceLock<Option<Mutex<Sink>>> = OnceLock::new();
+
+/// Open the sink requested by the environment, if any
+fn open() -> Option<Mutex<Sink>> {
+    let sink = match config::var("AUTOCC_TRACE") {
+        Some(t) if t.is_empty() || t == "0" => return None,
+        Some(t) if t == "1" || t == "stderr" => Sink::Stderr,
+        Some(path) => match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => Sink::File(file),
+            Err(err) => {
+                eprintln!("autocc: cannot open trace file {path}: {err}");
+                Sink::Stderr
+            }
+        },
+        None if config::flag("AUTOCC_DEBUG", false) => Sink::Stderr,
+        None => return None,
+    };
+
+    Some(Mutex::new(sink))
+}
+
+/// Capture the trace in memory instead, must be called before anything is traced
+pub fn capture() {
+    let _ = SINK.set(Some(Mutex::new(Sink::Memory(vec![]))));
+}
+
+/// Take the lines captured so far
+pub fn captured() -> Vec<String> {
+    match SINK.get() {
+        Some(Some(sink)) => match &mut *sink.lock().unwrap_or_else(|e| e.into_inner()) {
+            Sink::Memory(lines) => std::mem::take(lines),
+            _ => vec![],
+        },
+        _ => vec![],
+    }
+}
+
+/// Emit a trace line, tagged with our pid to untangle parallel builds
+pub fn write(args: Arguments<'_>) {
+    let Some(sink) = SINK.get_or_init(open) else {
+        return;
+    };
+
+    let mut sink = sink.lock().unwrap_or_else(|e| e.into_inner());
+    let _ = match &mut *sink {
+        Sink::Stderr => writeln!(io::stderr(), "autocc[{}]: {args}", process::id()),
+        Sink::File(file) => writeln!(file, "autocc[{}]: {args}", process::id()),
+        Sink::Memory(lines) => {
+            lines.push(args.to_string());
+            Ok(())
+        }
+    };
+}
+
+/// Trace a resolution step
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::trace::write(format_args!($($arg)*))
+    };
+}
+
+pub(crate) use trace;
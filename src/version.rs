@@ -0,0 +1,94 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Dotted compiler versions (`18`, `13.2.0`)
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// A version compared component-wise, so `13` < `13.2` < `14`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Version(Vec<u32>);
+
+impl Version {
+    /// Major component
+    pub fn major(&self) -> u32 {
+        self.0.first().copied().unwrap_or_default()
+    }
+
+    /// Whether this version is the given one or a more specific form of it (`18.1` matches `18`)
+    pub fn matches(&self, other: &Version) -> bool {
+        self.0.starts_with(&other.0)
+    }
+}
+
+impl FromStr for Version {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .trim()
+            .split('.')
+            .map(|p| p.parse::<u32>().map_err(|_| ()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self(parts))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts = self.0.iter().map(u32::to_string).collect::<Vec<_>>();
+        write!(f, "{}", parts.join("."))
+    }
+}
+
+impl From<Version> for String {
+    fn from(version: Version) -> Self {
+        version.to_string()
+    }
+}
+
+impl TryFrom<String> for Version {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse().map_err(|()| format!("invalid version {s:?}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Version;
+
+    fn v(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(v("13.2.0").to_string(), "13.2.0");
+        assert_eq!(v(" 18 ").major(), 18);
+        assert!("".parse::<Version>().is_err());
+        assert!("18.x".parse::<Version>().is_err());
+        assert!("-18".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn ordering() {
+        assert!(v("13") < v("13.2"));
+        assert!(v("13.2") < v("14"));
+        assert!(v("9.5") < v("10"));
+    }
+
+    #[test]
+    fn matches() {
+        assert!(v("18.1.8").matches(&v("18")));
+        assert!(v("18.1.8").matches(&v("18.1")));
+        assert!(!v("18").matches(&v("18.1")));
+        assert!(!v("181").matches(&v("18")));
+    }
+}
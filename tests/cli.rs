@@ -0,0 +1,25 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{env, process::Command};
+
+#[test]
+fn explain_traces_target() {
+    let root = env::temp_dir().join(format!("autocc-test-{}", std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_autocc"))
+        .args(["explain", "cc", "-c", "x.c"])
+        .env_clear()
+        .env("PATH", root.join("bin"))
+        .env("HOME", &root)
+        .env("CHOST", "aarch64-linux-gnu")
+        .output()
+        .unwrap();
+
+    // Resolution fails without compilers, so the trace is on stderr
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("targeting aarch64-linux-gnu"),
+        "no target in trace: {stderr}"
+    );
+}